@@ -93,7 +93,7 @@ pub fn criterion_benchmark(c: &mut Criterion) {
         let mut template = None;
 
         b.iter(|| {
-            template = Some(Template::parse(PAGE.to_string()));
+            template = Some(Template::parse(PAGE.to_string()).unwrap());
         });
     });
 
@@ -114,7 +114,7 @@ pub fn criterion_benchmark(c: &mut Criterion) {
     });
 
     c.bench_function("render", |b| {
-        let t = Template::parse(PAGE.to_string());
+        let t = Template::parse(PAGE.to_string()).unwrap();
         let mut map = HashMap::new();
         map.insert("template".to_owned(), t);
         let templates = Templates::new(map);
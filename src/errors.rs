@@ -1 +1,54 @@
-pub type Error = std::io::Error;
+use templet_parser::Position;
+use thiserror::Error;
+
+use crate::templates::TemplateLoadError;
+
+/// Error returned when rendering a template fails.
+#[derive(Debug, Error)]
+pub enum RenderError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error("template `{name}` not found")]
+    MissingTemplate { name: String },
+    #[error("`{path}` not found in template `{template}` at {position}")]
+    MissingPath {
+        template: String,
+        path: String,
+        position: Position,
+    },
+    #[error("included template `{name}` not found in template `{template}` at {position}")]
+    MissingInclude {
+        template: String,
+        name: String,
+        position: Position,
+    },
+    #[error("cannot render `{path}` of type `{type_name}` in template `{template}` at {position}")]
+    UnsupportedValue {
+        template: String,
+        path: String,
+        type_name: String,
+        position: Position,
+    },
+    #[error("include cycle {} in template `{template}` at {position}", .chain.join(" -> "))]
+    IncludeCycle {
+        template: String,
+        chain: Vec<String>,
+        position: Position,
+    },
+    #[error("included template `{name}` is outside the template root in template `{template}` at {position}")]
+    IncludeOutsideRoot {
+        template: String,
+        name: String,
+        position: Position,
+    },
+    #[error("include depth limit of {limit} exceeded in template `{template}` at {position}")]
+    LimitExceeded {
+        template: String,
+        limit: usize,
+        position: Position,
+    },
+    #[error("rendered output is not valid UTF-8")]
+    Utf8(#[from] std::string::FromUtf8Error),
+    #[error("failed to reload templates")]
+    Reload(#[source] TemplateLoadError),
+}
@@ -1,9 +1,16 @@
 mod errors;
-mod parse;
+mod loader;
 mod reflect_render;
+mod reload;
 mod template;
 mod templates;
 
+pub use errors::RenderError;
+pub use loader::{DirLoader, LayeredLoader, MemoryLoader, TemplateLoader, TemplateSource};
 pub use reflect_render::Unescaped;
+pub use reload::ReloadingTemplates;
 pub use template::Template;
-pub use templates::{TemplateLoadError, Templates};
+pub use templates::{LoadOptions, TemplateLoadError, Templates};
+#[cfg(feature = "embed")]
+pub use templet_macros::include_templates;
+pub use templet_parser::{ParseError, ParseErrorKind, ParseOptions, Position, ValidationError};
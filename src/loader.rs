@@ -0,0 +1,213 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use crate::templates::{LoadOptions, TemplateLoadError};
+
+/// Finds template sources by name, for [`Templates::load_from`].
+///
+/// [`Templates::load_from`]: crate::Templates::load_from
+pub trait TemplateLoader {
+    /// Names of every template the loader can load.
+    fn names(&self) -> Result<Vec<String>, TemplateLoadError>;
+
+    /// Names of the templates the loader can load, pushing the errors of
+    /// those it failed to name to `errors` instead of stopping at the first
+    /// one, for [`LoadOptions::collect_errors`].
+    ///
+    /// [`LoadOptions::collect_errors`]: crate::LoadOptions::collect_errors
+    fn collect_names(&self, errors: &mut Vec<TemplateLoadError>) -> Vec<String> {
+        self.names().unwrap_or_else(|err| {
+            errors.push(err);
+            Vec::new()
+        })
+    }
+
+    /// Loads the template `name`, or returns `None` if the loader has no
+    /// template of that name.
+    fn load(&self, name: &str) -> Result<Option<TemplateSource>, TemplateLoadError>;
+
+    /// When the template `name` last changed, if the loader can tell. Used
+    /// by [`ReloadingTemplates`] to find the templates to reload.
+    ///
+    /// [`ReloadingTemplates`]: crate::ReloadingTemplates
+    fn modified(&self, _name: &str) -> Option<SystemTime> {
+        None
+    }
+}
+
+/// Source of a template found by a [`TemplateLoader`].
+#[derive(Clone, Debug)]
+pub struct TemplateSource {
+    pub source: String,
+    /// Where the source was loaded from, used in errors.
+    pub path: PathBuf,
+}
+
+/// Loads the files of a directory, named according to [`LoadOptions`].
+#[derive(Clone, Debug)]
+pub struct DirLoader {
+    dir: PathBuf,
+    options: LoadOptions,
+}
+
+impl DirLoader {
+    pub fn new(dir: impl Into<PathBuf>, options: LoadOptions) -> Self {
+        Self {
+            dir: dir.into(),
+            options,
+        }
+    }
+
+    /// The file of the template `name`, if it exists.
+    fn file(&self, name: &str) -> Option<PathBuf> {
+        self.options
+            .file_candidates(name)
+            .into_iter()
+            .map(|path| self.dir.join(path))
+            .find(|path| path.is_file())
+    }
+}
+
+impl TemplateLoader for DirLoader {
+    fn names(&self) -> Result<Vec<String>, TemplateLoadError> {
+        let mut errors = Vec::new();
+        let names = self.collect_names(&mut errors);
+        match errors.into_iter().next() {
+            Some(err) => Err(err),
+            None => Ok(names),
+        }
+    }
+
+    fn collect_names(&self, errors: &mut Vec<TemplateLoadError>) -> Vec<String> {
+        let mut names = HashSet::new();
+        for entry in walkdir::WalkDir::new(&self.dir) {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(err) => {
+                    errors.push(err.into());
+                    continue;
+                }
+            };
+            let path = entry.path();
+            if !entry.file_type().is_file() || !self.options.has_extension(path) {
+                continue;
+            }
+            let name = match path.strip_prefix(&self.dir) {
+                Ok(relative) => self.options.template_name(relative),
+                Err(err) => {
+                    errors.push(err.into());
+                    continue;
+                }
+            };
+            if names.contains(&name) {
+                errors.push(TemplateLoadError::DuplicateName {
+                    name,
+                    path: path.to_owned(),
+                });
+                continue;
+            }
+            names.insert(name);
+        }
+        names.into_iter().collect()
+    }
+
+    fn load(&self, name: &str) -> Result<Option<TemplateSource>, TemplateLoadError> {
+        let path = match self.file(name) {
+            Some(path) => path,
+            None => return Ok(None),
+        };
+        let source = fs::read_to_string(&path).map_err(|source| TemplateLoadError::Io {
+            path: path.clone(),
+            source,
+        })?;
+        Ok(Some(TemplateSource { source, path }))
+    }
+
+    fn modified(&self, name: &str) -> Option<SystemTime> {
+        fs::metadata(self.file(name)?).ok()?.modified().ok()
+    }
+}
+
+/// Loads templates from sources held in memory.
+#[derive(Clone, Debug, Default)]
+pub struct MemoryLoader {
+    sources: HashMap<String, String>,
+}
+
+impl MemoryLoader {
+    pub fn new(sources: HashMap<String, String>) -> Self {
+        Self { sources }
+    }
+
+    pub fn insert(&mut self, name: impl Into<String>, source: impl Into<String>) {
+        self.sources.insert(name.into(), source.into());
+    }
+}
+
+impl TemplateLoader for MemoryLoader {
+    fn names(&self) -> Result<Vec<String>, TemplateLoadError> {
+        Ok(self.sources.keys().cloned().collect())
+    }
+
+    fn load(&self, name: &str) -> Result<Option<TemplateSource>, TemplateLoadError> {
+        Ok(self.sources.get(name).map(|source| TemplateSource {
+            source: source.clone(),
+            path: Path::new(name).to_owned(),
+        }))
+    }
+}
+
+/// Loads each template from the first of its loaders that has it, e.g. a
+/// theme directory overriding some of the default templates.
+#[derive(Default)]
+pub struct LayeredLoader {
+    layers: Vec<Box<dyn TemplateLoader + Send + Sync>>,
+}
+
+impl LayeredLoader {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a loader, used for the templates none of the earlier loaders
+    /// have.
+    pub fn layer(mut self, loader: impl TemplateLoader + Send + Sync + 'static) -> Self {
+        self.layers.push(Box::new(loader));
+        self
+    }
+}
+
+impl TemplateLoader for LayeredLoader {
+    fn names(&self) -> Result<Vec<String>, TemplateLoadError> {
+        let mut names = HashSet::new();
+        for layer in &self.layers {
+            names.extend(layer.names()?);
+        }
+        Ok(names.into_iter().collect())
+    }
+
+    fn collect_names(&self, errors: &mut Vec<TemplateLoadError>) -> Vec<String> {
+        let mut names = HashSet::new();
+        for layer in &self.layers {
+            names.extend(layer.collect_names(errors));
+        }
+        names.into_iter().collect()
+    }
+
+    fn load(&self, name: &str) -> Result<Option<TemplateSource>, TemplateLoadError> {
+        for layer in &self.layers {
+            if let Some(source) = layer.load(name)? {
+                return Ok(Some(source));
+            }
+        }
+        Ok(None)
+    }
+
+    fn modified(&self, name: &str) -> Option<SystemTime> {
+        self.layers.iter().find_map(|layer| layer.modified(name))
+    }
+}
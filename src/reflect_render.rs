@@ -1,6 +1,6 @@
-use std::{collections::HashMap, io::Write};
+use std::{any::TypeId, io::Write};
 
-use bevy_reflect::{Enum, FromReflect, GetTypeRegistration, VariantType};
+use bevy_reflect::{Enum, FromReflect, VariantType};
 use bevy_reflect::{
     Reflect,
     ReflectRef::{self, *},
@@ -8,196 +8,532 @@ use bevy_reflect::{
 use convert_case::{Case, Casing};
 use v_htmlescape::escape;
 
-use crate::parse::Access;
-use crate::{
-    parse::{Field, Part},
-    template::Template,
-};
+use templet_parser::parse::{Access, Field, IncludeName, Part};
+use templet_parser::{include_key, Position};
+
+use crate::errors::RenderError;
+use crate::templates::Templates;
+
+#[derive(Clone, Debug)]
+pub(crate) struct RenderOptions {
+    pub strict: bool,
+    pub max_include_depth: usize,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        RenderOptions {
+            strict: false,
+            max_include_depth: 64,
+        }
+    }
+}
 
 pub struct Renderer<'a, W> {
-    templates: &'a HashMap<String, Template>,
+    templates: &'a Templates,
     writer: &'a mut W,
+    options: &'a RenderOptions,
+    template: &'a str,
+    /// Key of the template the parts being rendered were written in.
+    path: &'a str,
+    /// Templates being rendered, with the identity of the data they were
+    /// rendered with and of the blocks overriding theirs, from the outermost
+    /// one inwards.
+    includes: Vec<(&'a str, DataId, Vec<*const [Part<'a>]>)>,
+    /// Indentation of the standalone includes being rendered.
+    indent: String,
+    /// Whether `indent` is due before the next output.
+    line_start: bool,
+    /// Blocks given to the parent templates being rendered, with the key of
+    /// the template defining them, from the outermost child inwards.
+    blocks: Vec<(&'a Access<'a>, &'a [Part<'a>], &'a str)>,
+    /// Inline partials in scope, from the outermost one inwards.
+    inlines: Vec<Partial<'a>>,
+}
+
+/// Parts rendered by an include or parent tag.
+#[derive(Clone, Copy)]
+struct Partial<'a> {
+    name: &'a str,
+    parts: &'a [Part<'a>],
+    /// Key of the template the parts were written in, which relative
+    /// includes within them are resolved against.
+    path: &'a str,
 }
 
 impl<'a, W: Write> Renderer<'a, W> {
-    pub fn new(templates: &'a HashMap<String, Template>, writer: &'a mut W) -> Self {
-        Self { templates, writer }
+    pub fn new(templates: &'a Templates, writer: &'a mut W, options: &'a RenderOptions) -> Self {
+        Self {
+            templates,
+            writer,
+            options,
+            template: "",
+            path: "",
+            includes: Vec::new(),
+            indent: String::new(),
+            line_start: false,
+            blocks: Vec::new(),
+            inlines: Vec::new(),
+        }
     }
 
-    pub fn render<T: Reflect + GetTypeRegistration>(
-        &mut self,
-        template: &str,
-        data: &T,
-    ) -> Result<(), std::io::Error> {
-        if let Some(template) = self.templates.get(template) {
-            let parts = template.parts();
-            self.render_parts(parts, data)?;
+    pub fn render<T: Reflect>(&mut self, template: &str, data: &T) -> Result<(), RenderError> {
+        match self.templates.get(template) {
+            Some((name, template)) => {
+                self.template = name;
+                self.path = name;
+                self.includes.push((name, data_id(data), Vec::new()));
+                self.render_parts(template.parts(), &Context::new(data))
+            }
+            None if self.options.strict => Err(RenderError::MissingTemplate {
+                name: template.to_owned(),
+            }),
+            None => Ok(()),
         }
-        Ok(())
     }
 
-    fn render_parts(&mut self, parts: &[Part], data: &dyn Reflect) -> Result<(), std::io::Error> {
+    fn render_parts(
+        &mut self,
+        parts: &'a [Part<'a>],
+        context: &Context,
+    ) -> Result<(), RenderError> {
+        let scope = self.inlines.len();
+        self.define_inlines(parts);
         for part in parts.iter() {
+            // Tags starting a line of an indented include are indented like
+            // text, but standalone includes only add to the indentation.
             match part {
-                Part::Text(text) => write!(self.writer, "{}", text)?,
-                Part::Variable(access) => {
-                    if let Some(data) = get_path(data, access) {
-                        self.render_value(data)?;
+                Part::Text(_)
+                | Part::Comment
+                | Part::Block(..)
+                | Part::Parent(..)
+                | Part::Inline(..) => {}
+                Part::Include(include) if !include.indent.is_empty() => {}
+                _ => self.write_indent()?,
+            }
+            match part {
+                Part::Text(text) => self.write_text(text)?,
+                Part::Variable(access, position) => {
+                    if let Some(value) = self.lookup(context, access, *position)? {
+                        self.render_value(value, true, access, *position)?;
+                    }
+                }
+                Part::UnescapedVariable(access, position) => {
+                    if let Some(value) = self.lookup(context, access, *position)? {
+                        self.render_value(value, false, access, *position)?;
                     }
                 }
-                Part::Section(access, parts) => {
-                    if let Some(data) = get_path(data, access) {
+                Part::Section(access, position, parts) => {
+                    if let Some(data) = self.lookup(context, access, *position)? {
                         match data.reflect_ref() {
                             ReflectRef::List(list) => {
                                 for item in list.iter() {
-                                    self.render_parts(parts, item)?;
+                                    self.render_parts(parts, &context.push(item))?;
                                 }
                             }
                             ReflectRef::TupleStruct(st) => {
                                 for field in 0..st.field_len() {
                                     if let Some(value) = st.field(field) {
-                                        self.render_parts(parts, value)?;
+                                        self.render_parts(parts, &context.push(value))?;
                                     }
                                 }
                             }
                             ReflectRef::Tuple(st) => {
                                 for field in 0..st.field_len() {
                                     if let Some(value) = st.field(field) {
-                                        self.render_parts(parts, value)?;
+                                        self.render_parts(parts, &context.push(value))?;
                                     }
                                 }
                             }
                             ReflectRef::Array(arr) => {
                                 for item in arr.iter() {
-                                    self.render_parts(parts, item)?;
+                                    self.render_parts(parts, &context.push(item))?;
                                 }
                             }
                             ReflectRef::Value(val) if val.is::<bool>() => {
                                 if let Some(true) = val.downcast_ref::<bool>() {
-                                    self.render_parts(parts, data)?;
+                                    self.render_parts(parts, &context.push(data))?;
                                 }
                             }
                             ReflectRef::Value(val) if val.is::<String>() => {
                                 match val.downcast_ref::<String>() {
                                     None => {}
                                     Some(s) if s.is_empty() => {}
-                                    Some(_) => self.render_parts(parts, data)?,
+                                    Some(_) => self.render_parts(parts, &context.push(data))?,
                                 };
                             }
                             ReflectRef::Enum(enm) if is_option(enm) => {
                                 if let Some(val) = option_value(enm) {
-                                    self.render_parts(parts, val)?;
+                                    self.render_parts(parts, &context.push(val))?;
                                 }
                             }
-                            _ => self.render_parts(parts, data)?,
+                            _ => self.render_parts(parts, &context.push(data))?,
                         }
                     }
                 }
-                Part::InvertedSection(access, parts) => {
-                    let path_data = get_path(data, access);
+                Part::InvertedSection(access, position, parts) => {
+                    let path_data = self.lookup(context, access, *position)?;
                     match path_data.map(|data| data.reflect_ref()) {
                         None => {
-                            self.render_parts(parts, data)?;
+                            self.render_parts(parts, context)?;
                         }
                         Some(ReflectRef::List(list)) if list.is_empty() => {
-                            self.render_parts(parts, data)?;
+                            self.render_parts(parts, context)?;
                         }
                         Some(ReflectRef::Array(arr)) if arr.is_empty() => {
-                            self.render_parts(parts, data)?;
+                            self.render_parts(parts, context)?;
                         }
                         Some(ReflectRef::Enum(enm))
                             if is_option(enm) && enm.variant_name() == "None" =>
                         {
-                            self.render_parts(parts, data)?;
+                            self.render_parts(parts, context)?;
                         }
                         Some(ReflectRef::Value(val)) if val.is::<bool>() => {
                             if let Some(false) = val.downcast_ref::<bool>() {
-                                self.render_parts(parts, data)?;
+                                self.render_parts(parts, context)?;
                             }
                         }
                         Some(ReflectRef::Value(val)) if val.is::<String>() => {
                             match val.downcast_ref::<String>() {
-                                Some(s) if s.is_empty() => self.render_parts(parts, data)?,
+                                Some(s) if s.is_empty() => self.render_parts(parts, context)?,
                                 _ => {}
                             };
                         }
                         _ => {}
                     }
                 }
-                Part::Include(name) => {
-                    if let Some(template) = &self.templates.get(*name) {
-                        self.render_parts(template.parts(), data)?;
+                Part::Include(include) => {
+                    let name = match &include.name {
+                        IncludeName::Path(path) => *path,
+                        IncludeName::Dynamic(access) => {
+                            match self.template_name(context, access, include.position)? {
+                                Some(name) => name,
+                                None => continue,
+                            }
+                        }
+                    };
+                    let base = match &include.context {
+                        Some(access) => match self.lookup(context, access, include.position)? {
+                            Some(value) => context.push(value),
+                            None => continue,
+                        },
+                        None => context.clone(),
+                    };
+                    let mut args = Vec::with_capacity(include.args.len());
+                    for (name, access) in &include.args {
+                        args.push((*name, self.lookup(&base, access, include.position)?));
+                    }
+                    let with_args;
+                    let context = if args.is_empty() {
+                        &base
+                    } else {
+                        with_args = Context {
+                            frame: Frame::Args(&args),
+                            parent: Some(&base),
+                        };
+                        &with_args
+                    };
+                    if let Some(partial) = self.resolve(name, include.position)? {
+                        self.render_include(partial, context, include.position, include.indent)?;
+                    }
+                }
+                Part::Block(name, _, parts) => {
+                    match self.blocks.iter().position(|(block, ..)| *block == name) {
+                        // Blocks given by the same or inner children do not
+                        // apply within the block that overrides this one.
+                        Some(i) => {
+                            let inner = self.blocks.split_off(i);
+                            let (_, parts, path) = inner[0];
+                            let parent = std::mem::replace(&mut self.path, path);
+                            let result = self.render_parts(parts, context);
+                            self.path = parent;
+                            self.blocks.extend(inner);
+                            result?;
+                        }
+                        None => self.render_parts(parts, context)?,
+                    }
+                }
+                Part::Parent(name, position, blocks) => {
+                    if let Some(partial) = self.resolve(name, *position)? {
+                        let outer = (self.blocks.len(), self.inlines.len());
+                        for block in blocks {
+                            if let Part::Block(name, _, parts) = block {
+                                self.blocks.push((name, parts, self.path));
+                            }
+                        }
+                        self.define_inlines(blocks);
+                        let result = self.render_include(partial, context, *position, "");
+                        self.blocks.truncate(outer.0);
+                        self.inlines.truncate(outer.1);
+                        result?;
                     }
                 }
-                Part::Comment => {}
+                Part::Inline(..) | Part::Comment => {}
             }
         }
+        self.inlines.truncate(scope);
         Ok(())
     }
 
-    fn render_value(&mut self, value: &dyn Reflect) -> Result<(), std::io::Error> {
+    /// The blocks in effect, the outermost one given for each name. A parent
+    /// rendered again with different blocks renders differently, so it is
+    /// not a cycle.
+    fn active_blocks(&self) -> Vec<*const [Part<'a>]> {
+        self.blocks
+            .iter()
+            .enumerate()
+            .filter(|(i, (name, ..))| !self.blocks[..*i].iter().any(|(outer, ..)| outer == name))
+            .map(|(_, (_, parts, _))| *parts as *const [Part])
+            .collect()
+    }
+
+    /// Brings the inline partials defined in `parts` into scope, so that
+    /// they can be included before their definition too.
+    fn define_inlines(&mut self, parts: &'a [Part<'a>]) {
+        for part in parts {
+            if let Part::Inline(name, _, parts) = part {
+                self.inlines.push(Partial {
+                    name,
+                    parts,
+                    path: self.path,
+                });
+            }
+        }
+    }
+
+    fn render_include(
+        &mut self,
+        partial: Partial<'a>,
+        context: &Context,
+        position: Position,
+        indent: &str,
+    ) -> Result<(), RenderError> {
+        let Partial { name, parts, path } = partial;
+        let include = (name, context.id(), self.active_blocks());
+        if let Some(start) = self.includes.iter().position(|entry| *entry == include) {
+            let mut chain: Vec<String> = self.includes[start..]
+                .iter()
+                .map(|(name, ..)| name.to_string())
+                .collect();
+            chain.push(name.to_owned());
+            return Err(RenderError::IncludeCycle {
+                template: self.template.to_owned(),
+                chain,
+                position,
+            });
+        }
+        if self.includes.len() > self.options.max_include_depth {
+            return Err(RenderError::LimitExceeded {
+                template: self.template.to_owned(),
+                limit: self.options.max_include_depth,
+                position,
+            });
+        }
+
+        self.includes.push(include);
+        let parent = std::mem::replace(&mut self.template, name);
+        let parent_path = std::mem::replace(&mut self.path, path);
+        let parent_indent = self.indent.len();
+        if !indent.is_empty() {
+            self.indent.push_str(indent);
+            self.line_start = true;
+        }
+        let result = self.render_parts(parts, context);
+        self.indent.truncate(parent_indent);
+        self.path = parent_path;
+        self.template = parent;
+        self.includes.pop();
+        result
+    }
+
+    /// Writes template text, indenting every line of it when rendering an
+    /// indented include.
+    fn write_text(&mut self, text: &str) -> Result<(), std::io::Error> {
+        if self.indent.is_empty() {
+            return self.writer.write_all(text.as_bytes());
+        }
+        for line in text.split_inclusive('\n') {
+            self.write_indent()?;
+            self.writer.write_all(line.as_bytes())?;
+            self.line_start = line.ends_with('\n');
+        }
+        Ok(())
+    }
+
+    fn write_indent(&mut self) -> Result<(), std::io::Error> {
+        if self.line_start {
+            self.line_start = false;
+            self.writer.write_all(self.indent.as_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// Looks up an included or parent template, preferring the innermost
+    /// inline partial of the name, failing in strict mode when it does not
+    /// exist.
+    fn resolve(&self, name: &str, position: Position) -> Result<Option<Partial<'a>>, RenderError> {
+        if let Some(inline) = self.inlines.iter().rev().find(|inline| inline.name == name) {
+            return Ok(Some(*inline));
+        }
+        let key = include_key(self.path, name).ok_or_else(|| RenderError::IncludeOutsideRoot {
+            template: self.template.to_owned(),
+            name: name.to_owned(),
+            position,
+        })?;
+        match self.templates.get(&key) {
+            Some((name, template)) => Ok(Some(Partial {
+                name,
+                parts: template.parts(),
+                path: name,
+            })),
+            None if self.options.strict => Err(RenderError::MissingInclude {
+                template: self.template.to_owned(),
+                name: key.into_owned(),
+                position,
+            }),
+            None => Ok(None),
+        }
+    }
+
+    /// Looks up the name of a dynamically included template.
+    fn template_name<'r>(
+        &self,
+        context: &Context<'r>,
+        access: &Access,
+        position: Position,
+    ) -> Result<Option<&'r str>, RenderError> {
+        let value = match self.lookup(context, access, position)? {
+            Some(value) => value,
+            None => return Ok(None),
+        };
+        let value = match value.reflect_ref() {
+            ReflectRef::Enum(enm) if is_option(enm) => match option_value(enm) {
+                Some(value) => value,
+                None => return Ok(None),
+            },
+            _ => value,
+        };
+        match value.downcast_ref::<String>() {
+            Some(name) => Ok(Some(name)),
+            None if self.options.strict => Err(RenderError::UnsupportedValue {
+                template: self.template.to_owned(),
+                path: access.to_string(),
+                type_name: value.type_name().to_owned(),
+                position,
+            }),
+            None => Ok(None),
+        }
+    }
+
+    /// Resolves `access` against `context`, failing in strict mode when the
+    /// path does not exist.
+    fn lookup<'r>(
+        &self,
+        context: &Context<'r>,
+        access: &Access,
+        position: Position,
+    ) -> Result<Option<&'r dyn Reflect>, RenderError> {
+        match get_path(context, access) {
+            Ok(value) => Ok(value),
+            Err(Missing) if self.options.strict => Err(RenderError::MissingPath {
+                template: self.template.to_owned(),
+                path: access.to_string(),
+                position,
+            }),
+            Err(Missing) => Ok(None),
+        }
+    }
+
+    fn render_value(
+        &mut self,
+        value: &dyn Reflect,
+        escaped: bool,
+        access: &Access,
+        position: Position,
+    ) -> Result<(), RenderError> {
+        if self.write_primitive(value, escaped)? {
+            return Ok(());
+        }
+        match value.reflect_ref() {
+            ReflectRef::Enum(enm) if is_option(enm) => {
+                if let Some(value) = option_value(enm) {
+                    self.render_value(value, escaped, access, position)?;
+                }
+                Ok(())
+            }
+            ReflectRef::Enum(enm) if enm.is_variant(VariantType::Unit) => {
+                let variant_name = enm.variant_name().to_case(Case::Kebab);
+                write!(self.writer, "{}", &variant_name)?;
+                Ok(())
+            }
+            ReflectRef::Enum(enm) if enm.is_variant(VariantType::Tuple) && enm.field_len() == 1 => {
+                self.render_value(enm.field_at(0).unwrap(), escaped, access, position)
+            }
+            ReflectRef::TupleStruct(st) if st.field_len() == 1 => {
+                self.render_value(st.field(0).unwrap(), escaped, access, position)
+            }
+            _ if self.options.strict => Err(RenderError::UnsupportedValue {
+                template: self.template.to_owned(),
+                path: access.to_string(),
+                type_name: value.type_name().to_owned(),
+                position,
+            }),
+            _ => {
+                let type_name = value.type_name();
+                write!(self.writer, "UNSUPPORTED_VARIABLE_VALUE({})", type_name)?;
+                Ok(())
+            }
+        }
+    }
+
+    /// Writes `value` if it is a primitive, returning whether it was written.
+    fn write_primitive(
+        &mut self,
+        value: &dyn Reflect,
+        escaped: bool,
+    ) -> Result<bool, std::io::Error> {
         if let Some(b) = value.downcast_ref::<bool>() {
-            write!(self.writer, "{}", b)
+            write!(self.writer, "{}", b)?;
         } else if let Some(n) = value.downcast_ref::<u8>() {
-            write!(self.writer, "{}", n)
+            write!(self.writer, "{}", n)?;
         } else if let Some(n) = value.downcast_ref::<u16>() {
-            write!(self.writer, "{}", n)
+            write!(self.writer, "{}", n)?;
         } else if let Some(n) = value.downcast_ref::<u32>() {
-            write!(self.writer, "{}", n)
+            write!(self.writer, "{}", n)?;
         } else if let Some(n) = value.downcast_ref::<u64>() {
-            write!(self.writer, "{}", n)
+            write!(self.writer, "{}", n)?;
         } else if let Some(n) = value.downcast_ref::<u128>() {
-            write!(self.writer, "{}", n)
+            write!(self.writer, "{}", n)?;
         } else if let Some(n) = value.downcast_ref::<usize>() {
-            write!(self.writer, "{}", n)
+            write!(self.writer, "{}", n)?;
         } else if let Some(n) = value.downcast_ref::<i8>() {
-            write!(self.writer, "{}", n)
+            write!(self.writer, "{}", n)?;
         } else if let Some(n) = value.downcast_ref::<i16>() {
-            write!(self.writer, "{}", n)
+            write!(self.writer, "{}", n)?;
         } else if let Some(n) = value.downcast_ref::<i32>() {
-            write!(self.writer, "{}", n)
+            write!(self.writer, "{}", n)?;
         } else if let Some(n) = value.downcast_ref::<i64>() {
-            write!(self.writer, "{}", n)
+            write!(self.writer, "{}", n)?;
         } else if let Some(n) = value.downcast_ref::<i128>() {
-            write!(self.writer, "{}", n)
+            write!(self.writer, "{}", n)?;
         } else if let Some(n) = value.downcast_ref::<isize>() {
-            write!(self.writer, "{}", n)
+            write!(self.writer, "{}", n)?;
         } else if let Some(n) = value.downcast_ref::<f32>() {
-            write!(self.writer, "{}", n)
+            write!(self.writer, "{}", n)?;
         } else if let Some(n) = value.downcast_ref::<f64>() {
-            write!(self.writer, "{}", n)
+            write!(self.writer, "{}", n)?;
         } else if let Some(s) = value.downcast_ref::<String>() {
-            let escaped = escape(s.as_str());
-            write!(self.writer, "{}", escaped)
+            if escaped {
+                write!(self.writer, "{}", escape(s.as_str()))?;
+            } else {
+                write!(self.writer, "{}", s)?;
+            }
         } else if let Some(u) = value.downcast_ref::<Unescaped>() {
-            write!(self.writer, "{}", u.0)
+            write!(self.writer, "{}", u.0)?;
         } else {
-            match value.reflect_ref() {
-                ReflectRef::Enum(enm) if is_option(enm) => {
-                    if let Some(value) = option_value(enm) {
-                        self.render_value(value)?;
-                    }
-                    Ok(())
-                }
-                ReflectRef::Enum(enm) if enm.is_variant(VariantType::Unit) => {
-                    let variant_name = enm.variant_name().to_case(Case::Kebab);
-                    write!(self.writer, "{}", &variant_name)?;
-                    Ok(())
-                }
-                ReflectRef::Enum(enm)
-                    if enm.is_variant(VariantType::Tuple) && enm.field_len() == 1 =>
-                {
-                    self.render_value(enm.field_at(0).unwrap())
-                }
-                ReflectRef::TupleStruct(st) if st.field_len() == 1 => {
-                    self.render_value(st.field(0).unwrap())
-                }
-                _ => {
-                    let type_name = value.type_name();
-                    write!(self.writer, "UNSUPPORTED_VARIABLE_VALUE({})", type_name)
-                }
-            }
+            return Ok(false);
         }
+        Ok(true)
     }
 }
 
@@ -210,17 +546,114 @@ impl From<String> for Unescaped {
     }
 }
 
-fn get_path<'r, 'a>(reflect: &'r dyn Reflect, access: &'a Access<'a>) -> Option<&'r dyn Reflect> {
+/// Stack of values entered through sections, innermost first.
+#[derive(Clone)]
+struct Context<'c> {
+    frame: Frame<'c>,
+    parent: Option<&'c Context<'c>>,
+}
+
+#[derive(Clone, Copy)]
+enum Frame<'c> {
+    Value(&'c dyn Reflect),
+    /// Named arguments of an include. They shadow the fields of the
+    /// contexts below, but are skipped by `.`, parent and root paths.
+    Args(&'c [(&'c str, Option<&'c dyn Reflect>)]),
+}
+
+impl<'c> Context<'c> {
+    fn new(value: &'c dyn Reflect) -> Self {
+        Context {
+            frame: Frame::Value(value),
+            parent: None,
+        }
+    }
+
+    fn push(&'c self, value: &'c dyn Reflect) -> Context<'c> {
+        Context {
+            frame: Frame::Value(value),
+            parent: Some(self),
+        }
+    }
+
+    fn frames(&self) -> impl Iterator<Item = Frame<'c>> + '_ {
+        std::iter::successors(Some(self), |context| context.parent).map(|context| context.frame)
+    }
+
+    /// Context values from the innermost outwards.
+    fn values(&self) -> impl Iterator<Item = &'c dyn Reflect> + '_ {
+        self.frames().filter_map(|frame| match frame {
+            Frame::Value(value) => Some(value),
+            Frame::Args(_) => None,
+        })
+    }
+
+    fn value(&self) -> &'c dyn Reflect {
+        self.values().next().expect("context has a root value")
+    }
+
+    /// Identity of the context for include cycle detection. Arguments are
+    /// built for every include, so a context with them never repeats.
+    fn id(&self) -> DataId {
+        match self.frame {
+            Frame::Value(value) => data_id(value),
+            Frame::Args(args) => (args.as_ptr() as *const (), TypeId::of::<Frame<'static>>()),
+        }
+    }
+}
+
+/// Lookup failure for a path that does not exist in the data, as opposed to
+/// one that resolves to nothing (e.g. through a `None`).
+struct Missing;
+
+/// Resolves `access`. The first field of a plain path is looked up from the
+/// innermost context outwards, Mustache style; the rest of the path is
+/// resolved against the value it was found in. Parent and root paths are
+/// resolved against that exact context.
+fn get_path<'r, 'a>(
+    context: &Context<'r>,
+    access: &'a Access<'a>,
+) -> Result<Option<&'r dyn Reflect>, Missing> {
+    let reflect = context.value();
     match access {
-        Access::Variant(variant) => match reflect.reflect_ref() {
+        Access::Variant(variant) => Ok(match reflect.reflect_ref() {
             ReflectRef::Enum(enm) if is_option(enm) => option_value(enm),
             ReflectRef::Enum(enm) if enm.variant_name() == *variant => Some(reflect),
             _ => None,
-        },
-        Access::Path(fields) => get_fields(reflect, fields),
-        Access::This => match reflect.reflect_ref() {
+        }),
+        Access::Path(fields) => {
+            let (first, rest) = fields.split_first().ok_or(Missing)?;
+            for frame in context.frames() {
+                let found = match frame {
+                    Frame::Value(value) => get_field(value, first),
+                    Frame::Args(args) => match first {
+                        Field::Named(field) => args
+                            .iter()
+                            .find(|(name, _)| name == field)
+                            .map(|(_, value)| *value)
+                            .ok_or(Missing),
+                        _ => Err(Missing),
+                    },
+                };
+                match found {
+                    Ok(Some(value)) => return get_fields(value, rest),
+                    Ok(None) => return Ok(None),
+                    Err(Missing) => {}
+                }
+            }
+            Err(Missing)
+        }
+        Access::This => Ok(match reflect.reflect_ref() {
             ReflectRef::Enum(enm) if is_option(enm) => option_value(enm),
             _ => Some(reflect),
+        }),
+        Access::Parent(depth, fields) => match context.values().nth(*depth) {
+            Some(value) => get_fields(value, fields),
+            None => Err(Missing),
+        },
+        Access::Root(fields) => match context.values().last() {
+            Some(value) => get_fields(value, fields),
+            None => Err(Missing),
         },
     }
 }
@@ -228,30 +661,52 @@ fn get_path<'r, 'a>(reflect: &'r dyn Reflect, access: &'a Access<'a>) -> Option<
 fn get_fields<'r, 'f>(
     reflect: &'r dyn Reflect,
     fields: &'f [Field<'f>],
-) -> Option<&'r dyn Reflect> {
+) -> Result<Option<&'r dyn Reflect>, Missing> {
     let mut value = reflect;
     for field in fields.iter() {
-        value = get_field(value, field)?;
+        match get_field(value, field)? {
+            Some(field_value) => value = field_value,
+            None => return Ok(None),
+        }
     }
-    Some(value)
+    Ok(Some(value))
 }
 
-fn get_field<'r, 'f>(reflect: &'r dyn Reflect, field: &'f Field<'f>) -> Option<&'r dyn Reflect> {
+fn get_field<'r, 'f>(
+    reflect: &'r dyn Reflect,
+    field: &'f Field<'f>,
+) -> Result<Option<&'r dyn Reflect>, Missing> {
     match (field, reflect.reflect_ref()) {
-        (field, Enum(enm)) if is_option(enm) => {
-            option_value(enm).and_then(|value| get_field(value, field))
+        (field, Enum(enm)) if is_option(enm) => match option_value(enm) {
+            Some(value) => get_field(value, field),
+            None => Ok(None),
+        },
+        (Field::Index(i), List(list)) => Ok(list.get(*i)),
+        (Field::Index(i), Array(arr)) => Ok(arr.get(*i)),
+        (Field::Nth(n), TupleStruct(ts)) => ts.field(*n).map(Some).ok_or(Missing),
+        (Field::Nth(n), Tuple(t)) => t.field(*n).map(Some).ok_or(Missing),
+        (Field::Nth(n), Enum(enm)) if enm.is_variant(VariantType::Tuple) => {
+            enm.field_at(*n).map(Some).ok_or(Missing)
+        }
+        (Field::Named(name), Struct(s)) => s.field(name).map(Some).ok_or(Missing),
+        (Field::Named(name), Enum(enm)) if enm.is_variant(VariantType::Struct) => {
+            enm.field(name).map(Some).ok_or(Missing)
         }
-        (Field::Index(i), List(list)) => list.get(*i),
-        (Field::Index(i), Array(arr)) => arr.get(*i),
-        (Field::Nth(n), TupleStruct(ts)) => ts.field(*n),
-        (Field::Nth(n), Tuple(t)) => t.field(*n),
-        (Field::Nth(n), Enum(enm)) if enm.is_variant(VariantType::Tuple) => enm.field_at(*n),
-        (Field::Named(name), Struct(s)) => s.field(name),
-        (Field::Named(name), Enum(enm)) if enm.is_variant(VariantType::Struct) => enm.field(name),
-        _ => None,
+        _ => Err(Missing),
     }
 }
 
+/// Address and type of a value. Both are needed, as a struct shares its
+/// address with its first field.
+type DataId = (*const (), TypeId);
+
+fn data_id(data: &dyn Reflect) -> DataId {
+    (
+        data as *const dyn Reflect as *const (),
+        data.as_any().type_id(),
+    )
+}
+
 fn is_option(enm: &dyn Enum) -> bool {
     enm.type_name().starts_with("core::option::Option<")
 }
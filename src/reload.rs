@@ -0,0 +1,215 @@
+use std::{
+    collections::HashMap,
+    io::Write,
+    path::Path,
+    sync::{PoisonError, RwLock, RwLockReadGuard},
+    time::SystemTime,
+};
+
+use bevy_reflect::Reflect;
+
+use crate::{
+    errors::RenderError,
+    loader::{DirLoader, TemplateLoader},
+    templates::{load_template, LoadOptions, TemplateLoadError, Templates},
+};
+
+/// [`Templates`] that reload the templates changed since they were loaded
+/// before every render, for development.
+///
+/// Changes are found through [`TemplateLoader::modified`], so only loaders
+/// reporting modification times, like [`DirLoader`], are reloaded. Renders
+/// running while templates are reloaded keep using the previous ones.
+pub struct ReloadingTemplates {
+    loader: Box<dyn TemplateLoader + Send + Sync>,
+    options: LoadOptions,
+    reload: bool,
+    state: RwLock<State>,
+}
+
+struct State {
+    templates: Templates,
+    /// Modification times of the loaded templates.
+    modified: HashMap<String, Option<SystemTime>>,
+}
+
+impl ReloadingTemplates {
+    pub fn load_dir(
+        dir_path: impl AsRef<Path>,
+        extensions: &[&str],
+    ) -> Result<Self, TemplateLoadError> {
+        Self::load_dir_with(dir_path, &LoadOptions::new(extensions))
+    }
+
+    pub fn load_dir_with(
+        dir_path: impl AsRef<Path>,
+        options: &LoadOptions,
+    ) -> Result<Self, TemplateLoadError> {
+        let loader = DirLoader::new(dir_path.as_ref(), options.clone());
+        Self::load_from(loader, options)
+    }
+
+    pub fn load_from(
+        loader: impl TemplateLoader + Send + Sync + 'static,
+        options: &LoadOptions,
+    ) -> Result<Self, TemplateLoadError> {
+        let modified = modification_times(&loader)?;
+        let templates = Templates::load_from(&loader, options)?;
+        Ok(Self {
+            loader: Box::new(loader),
+            options: options.clone(),
+            reload: cfg!(debug_assertions),
+            state: RwLock::new(State {
+                templates,
+                modified,
+            }),
+        })
+    }
+
+    /// Whether to reload changed templates before rendering. Enabled by
+    /// default in debug builds only.
+    pub fn set_reload(&mut self, reload: bool) {
+        self.reload = reload;
+    }
+
+    /// The current templates, e.g. to change their render options.
+    pub fn templates_mut(&mut self) -> &mut Templates {
+        &mut self
+            .state
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner)
+            .templates
+    }
+
+    /// Reloads the templates changed since they were last loaded, and drops
+    /// the removed ones. Nothing changes when a template fails to load.
+    pub fn reload(&self) -> Result<(), TemplateLoadError> {
+        let modified = modification_times(&*self.loader)?;
+        let changed: Vec<&String> = {
+            let state = self.read();
+            if state.modified == modified {
+                return Ok(());
+            }
+            modified
+                .iter()
+                .filter(|(name, time)| state.modified.get(*name) != Some(time))
+                .map(|(name, _)| name)
+                .collect()
+        };
+
+        let mut loaded = Vec::with_capacity(changed.len());
+        for name in changed {
+            if let Some(template) = load_template(&*self.loader, name, &self.options)? {
+                loaded.push((name.clone(), template));
+            }
+        }
+
+        let mut state = self.state.write().unwrap_or_else(PoisonError::into_inner);
+        state.templates.retain(|name| modified.contains_key(name));
+        for (name, template) in loaded {
+            state.templates.insert(name, template);
+        }
+        state.modified = modified;
+        Ok(())
+    }
+
+    pub fn render<W: Write, T: Reflect>(
+        &self,
+        name: &str,
+        writer: &mut W,
+        data: &T,
+    ) -> Result<(), RenderError> {
+        if self.reload {
+            self.reload().map_err(RenderError::Reload)?;
+        }
+        self.read().templates.render(name, writer, data)
+    }
+
+    pub fn render_to_string<T: Reflect>(
+        &self,
+        name: &str,
+        data: &T,
+    ) -> Result<String, RenderError> {
+        if self.reload {
+            self.reload().map_err(RenderError::Reload)?;
+        }
+        self.read().templates.render_to_string(name, data)
+    }
+
+    fn read(&self) -> RwLockReadGuard<'_, State> {
+        self.state.read().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+fn modification_times(
+    loader: &dyn TemplateLoader,
+) -> Result<HashMap<String, Option<SystemTime>>, TemplateLoadError> {
+    Ok(loader
+        .names()?
+        .into_iter()
+        .map(|name| {
+            let modified = loader.modified(&name);
+            (name, modified)
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, thread, time::Duration};
+
+    use super::*;
+
+    /// Writes `contents` to `path` and sets its modification time to `secs`
+    /// seconds from now, so changes are seen even on file systems with
+    /// coarse modification times.
+    fn write_later(path: &Path, contents: &str, secs: u64) {
+        fs::write(path, contents).unwrap();
+        let file = fs::File::options().write(true).open(path).unwrap();
+        file.set_modified(SystemTime::now() + Duration::from_secs(secs))
+            .unwrap();
+    }
+
+    #[test]
+    fn reloads_changed_templates() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("index.html"), "v1 {{> \"nav.html\"}}").unwrap();
+        fs::write(dir.path().join("nav.html"), "nav").unwrap();
+
+        let mut templates = ReloadingTemplates::load_dir(dir.path(), &["html"]).unwrap();
+        templates.set_reload(true);
+        let first = templates.render_to_string("index.html", &()).unwrap();
+
+        write_later(&dir.path().join("index.html"), "v2 {{> \"nav.html\"}}", 10);
+        fs::remove_file(dir.path().join("nav.html")).unwrap();
+        let changed = thread::scope(|scope| {
+            let renders: Vec<_> = (0..4)
+                .map(|_| scope.spawn(|| templates.render_to_string("index.html", &())))
+                .collect();
+            renders
+                .into_iter()
+                .map(|render| render.join().unwrap().unwrap())
+                .collect::<Vec<_>>()
+        });
+
+        write_later(&dir.path().join("index.html"), "v3 {{#unclosed}}", 20);
+        let failed = templates.render_to_string("index.html", &());
+        let kept = templates
+            .read()
+            .templates
+            .render_to_string("index.html", &());
+
+        templates.set_reload(false);
+        fs::write(dir.path().join("index.html"), "v4").unwrap();
+        let disabled = templates.render_to_string("index.html", &());
+
+        assert_eq!(first, "v1 nav");
+        assert_eq!(changed, vec!["v2 "; 4]);
+        assert!(matches!(
+            failed,
+            Err(RenderError::Reload(TemplateLoadError::Parse { .. }))
+        ));
+        assert_eq!(kept.unwrap(), "v2 ");
+        assert_eq!(disabled.unwrap(), "v2 ");
+    }
+}
@@ -1,18 +1,20 @@
 use ouroboros::self_referencing;
-
-use crate::parse::{parse, Part};
+use templet_parser::{parse, parse::Part, ParseError, ParseOptions};
 
 pub struct Template(TemplateData);
 
 impl Template {
-    pub fn parse(input: String) -> Self {
-        Template(
-            TemplateDataBuilder {
-                source: input,
-                parts_builder: |str| parse(str.as_str()),
-            }
-            .build(),
-        )
+    pub fn parse(input: String) -> Result<Self, ParseError> {
+        Template::parse_with_options(input, &ParseOptions::default())
+    }
+
+    pub fn parse_with_options(input: String, options: &ParseOptions) -> Result<Self, ParseError> {
+        let data = TemplateDataTryBuilder {
+            source: input,
+            parts_builder: |str| parse(str.as_str(), options),
+        }
+        .try_build()?;
+        Ok(Template(data))
     }
 
     pub(crate) fn parts(&self) -> &[Part] {
@@ -1,94 +1,344 @@
-use std::{collections::HashMap, fs, io::Write, path::Path};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    io::Write,
+    path::{Component, Path, PathBuf},
+};
 
-use bevy_reflect::{GetTypeRegistration, Reflect, TypeRegistry};
+use bevy_reflect::Reflect;
+use templet_parser::{template_name, validate, ParseError, ParseOptions, ValidationError};
 use thiserror::Error;
 
-use crate::{errors::Error, reflect_render::Renderer, template::Template};
+use crate::{
+    errors::RenderError,
+    loader::{DirLoader, TemplateLoader, TemplateSource},
+    reflect_render::{RenderOptions, Renderer},
+    template::Template,
+};
 
 pub struct Templates {
     templates: HashMap<String, Template>,
-    type_registry: TypeRegistry,
+    /// Alternative names of templates.
+    aliases: HashMap<String, String>,
+    options: RenderOptions,
 }
 
 impl Templates {
     pub fn new(templates: HashMap<String, Template>) -> Self {
-        let mut type_registry = TypeRegistry::default();
-        type_registry.register::<String>();
         Self {
             templates,
-            type_registry,
+            aliases: HashMap::new(),
+            options: RenderOptions::default(),
         }
     }
 
-    pub fn register_type<T: GetTypeRegistration>(&mut self) {
-        self.type_registry.register::<T>();
+    /// In strict mode, rendering fails on missing templates, includes and
+    /// paths, and on values that cannot be rendered, instead of silently
+    /// skipping them.
+    pub fn set_strict(&mut self, strict: bool) {
+        self.options.strict = strict;
     }
 
-    pub fn render<W: Write, T: Reflect + GetTypeRegistration>(
+    /// How deeply includes and parent templates may nest, e.g. when a
+    /// template includes itself to render a tree. Rendering deeper fails
+    /// with [`RenderError::LimitExceeded`]. Defaults to 64.
+    pub fn set_max_include_depth(&mut self, depth: usize) {
+        self.options.max_include_depth = depth;
+    }
+
+    /// Checks the includes of every template, returning all includes of
+    /// missing templates and all include cycles that do not depend on the
+    /// rendered data. Dynamic includes are not checked.
+    pub fn validate(&self) -> Result<(), Vec<ValidationError>> {
+        let templates: BTreeMap<&str, &[_]> = self
+            .iter()
+            .map(|(name, template)| (name, template.parts()))
+            .collect();
+        validate(&templates, &self.aliases)
+    }
+
+    /// Looks up a template by name or alias, returning it with its name.
+    pub(crate) fn get(&self, name: &str) -> Option<(&str, &Template)> {
+        let name = match self.templates.contains_key(name) {
+            true => name,
+            false => self.aliases.get(name)?,
+        };
+        self.templates
+            .get_key_value(name)
+            .map(|(name, template)| (name.as_str(), template))
+    }
+
+    pub(crate) fn insert(&mut self, name: String, template: Template) {
+        self.templates.insert(name, template);
+    }
+
+    pub(crate) fn retain(&mut self, mut keep: impl FnMut(&str) -> bool) {
+        self.templates.retain(|name, _| keep(name));
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (&str, &Template)> {
+        self.templates
+            .iter()
+            .map(|(name, template)| (name.as_str(), template))
+    }
+
+    pub fn render<W: Write, T: Reflect>(
         &self,
         name: &str,
         writer: &mut W,
         data: &T,
-    ) -> Result<(), Error> {
-        let mut renderer = Renderer::new(&self.templates, writer);
+    ) -> Result<(), RenderError> {
+        let mut renderer = Renderer::new(self, writer, &self.options);
         renderer.render(name, data)
     }
 
-    pub fn render_to_string<T: Reflect + GetTypeRegistration>(
+    pub fn render_to_string<T: Reflect>(
         &self,
         name: &str,
         data: &T,
-    ) -> Result<String, Error> {
+    ) -> Result<String, RenderError> {
         let mut buf = Vec::new();
-        let mut renderer = Renderer::new(&self.templates, &mut buf);
+        let mut renderer = Renderer::new(self, &mut buf, &self.options);
         renderer.render(name, data)?;
-        Ok(String::from_utf8(buf).unwrap())
+        Ok(String::from_utf8(buf)?)
     }
 
     pub fn load_dir(
         dir_path: impl AsRef<Path>,
         extensions: &[&str],
     ) -> Result<Templates, TemplateLoadError> {
-        let dir_path: &Path = dir_path.as_ref();
+        Templates::load_dir_with(dir_path, &LoadOptions::new(extensions))
+    }
+
+    pub fn load_dir_with(
+        dir_path: impl AsRef<Path>,
+        options: &LoadOptions,
+    ) -> Result<Templates, TemplateLoadError> {
+        let loader = DirLoader::new(dir_path.as_ref(), options.clone());
+        Templates::load_from(&loader, options)
+    }
 
+    /// Loads and parses every template of `loader`. The naming options of
+    /// `options` only apply to the loaders using them, like [`DirLoader`].
+    pub fn load_from(
+        loader: &dyn TemplateLoader,
+        options: &LoadOptions,
+    ) -> Result<Templates, TemplateLoadError> {
         let mut templates: HashMap<String, Template> = HashMap::new();
-        for entry in walkdir::WalkDir::new(dir_path) {
-            let entry = entry?;
-            if entry.file_type().is_file()
-                && entry
-                    .path()
-                    .extension()
-                    .map(|ext| extensions.iter().any(|s| ext == *s))
-                    .unwrap_or(false)
-            {
-                let file = fs::read_to_string(entry.path())?;
-                let path = entry.path().strip_prefix(dir_path)?.to_string_lossy();
-                let template = Template::parse(file);
-                templates.insert(path.into_owned(), template);
+        let mut errors = Vec::new();
+        let names = match options.collect_errors {
+            true => loader.collect_names(&mut errors),
+            false => loader.names()?,
+        };
+        for name in names {
+            match load_template(loader, &name, options) {
+                Ok(Some(template)) => {
+                    templates.insert(name, template);
+                }
+                Ok(None) => {}
+                Err(err) if options.collect_errors => errors.push(err),
+                Err(err) => return Err(err),
             }
         }
-        Ok(Templates::new(templates))
+        for (alias, name) in &options.aliases {
+            if !templates.contains_key(name) {
+                let err = TemplateLoadError::MissingAliasTarget {
+                    alias: alias.clone(),
+                    name: name.clone(),
+                };
+                if !options.collect_errors {
+                    return Err(err);
+                }
+                errors.push(err);
+            }
+        }
+
+        if !errors.is_empty() {
+            return Err(TemplateLoadError::Multiple(errors));
+        }
+        let mut templates = Templates::new(templates);
+        templates.aliases = options.aliases.clone();
+        Ok(templates)
     }
 }
 
+pub(crate) fn load_template(
+    loader: &dyn TemplateLoader,
+    name: &str,
+    options: &LoadOptions,
+) -> Result<Option<Template>, TemplateLoadError> {
+    let TemplateSource { source, path } = match loader.load(name)? {
+        Some(source) => source,
+        None => return Ok(None),
+    };
+    let template =
+        Template::parse_with_options(source, &options.parse_options).map_err(|source| {
+            TemplateLoadError::Parse {
+                path,
+                source: Box::new(source),
+            }
+        })?;
+    Ok(Some(template))
+}
+
+/// Options for [`Templates::load_dir_with`] and [`Templates::load_from`].
+#[derive(Clone, Debug)]
+pub struct LoadOptions {
+    extensions: Vec<String>,
+    collect_errors: bool,
+    parse_options: ParseOptions,
+    strip_extensions: bool,
+    prefix: Option<String>,
+    aliases: HashMap<String, String>,
+}
+
+impl LoadOptions {
+    /// Loads files with the given extensions, stopping at the first error.
+    pub fn new(extensions: &[&str]) -> Self {
+        Self {
+            extensions: extensions.iter().map(|ext| ext.to_string()).collect(),
+            collect_errors: false,
+            parse_options: ParseOptions::default(),
+            strip_extensions: false,
+            prefix: None,
+            aliases: HashMap::new(),
+        }
+    }
+
+    /// When enabled, every file is loaded and all failures are returned
+    /// together as [`TemplateLoadError::Multiple`].
+    pub fn collect_errors(mut self, collect_errors: bool) -> Self {
+        self.collect_errors = collect_errors;
+        self
+    }
+
+    /// Options used to parse every loaded template.
+    pub fn parse_options(mut self, parse_options: ParseOptions) -> Self {
+        self.parse_options = parse_options;
+        self
+    }
+
+    /// Names templates without their file extension, `pages/index` instead
+    /// of `pages/index.html`.
+    pub fn strip_extensions(mut self, strip_extensions: bool) -> Self {
+        self.strip_extensions = strip_extensions;
+        self
+    }
+
+    /// Namespace prepended to every template name, `admin` naming
+    /// `pages/index.html` as `admin/pages/index.html`.
+    pub fn prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = Some(prefix.into());
+        self
+    }
+
+    /// Makes the template named `name` also available as `alias`. Template
+    /// names take precedence over aliases.
+    pub fn alias(mut self, alias: impl Into<String>, name: impl Into<String>) -> Self {
+        self.aliases.insert(alias.into(), name.into());
+        self
+    }
+
+    pub(crate) fn has_extension(&self, path: &Path) -> bool {
+        path.extension()
+            .map(|ext| self.extensions.iter().any(|s| ext == s.as_str()))
+            .unwrap_or(false)
+    }
+
+    /// Name of the template at `path`, relative to the loaded directory.
+    pub(crate) fn template_name(&self, path: &Path) -> String {
+        template_name(path, self.strip_extensions, self.prefix.as_deref())
+    }
+
+    /// Relative paths of the files that could be named `name`, the reverse
+    /// of [`LoadOptions::template_name`].
+    pub(crate) fn file_candidates(&self, name: &str) -> Vec<PathBuf> {
+        let relative = match self.prefix.as_deref() {
+            None | Some("") => Some(name),
+            Some(prefix) if prefix.ends_with('/') => name.strip_prefix(prefix),
+            Some(prefix) => name
+                .strip_prefix(prefix)
+                .and_then(|name| name.strip_prefix('/')),
+        };
+        let relative = match relative {
+            Some(relative) if is_relative_name(relative) => relative,
+            _ => return Vec::new(),
+        };
+        if self.strip_extensions {
+            self.extensions
+                .iter()
+                .map(|ext| PathBuf::from(format!("{}.{}", relative, ext)))
+                .collect()
+        } else if self.has_extension(Path::new(relative)) {
+            vec![PathBuf::from(relative)]
+        } else {
+            Vec::new()
+        }
+    }
+}
+
+/// Whether `name` stays inside the loaded directory: no root, drive prefix
+/// or `..` components.
+fn is_relative_name(name: &str) -> bool {
+    Path::new(name)
+        .components()
+        .all(|component| matches!(component, Component::Normal(_) | Component::CurDir))
+}
+
 #[derive(Debug, Error)]
 pub enum TemplateLoadError {
-    #[error("failed to load template files")]
-    Io(#[from] std::io::Error),
+    #[error("failed to read template file `{}`", .path.display())]
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
     #[error("could not strip path prefix")]
     StripPrefix(#[from] std::path::StripPrefixError),
     #[error("could not walk template directory")]
     WalkDir(#[from] walkdir::Error),
+    #[error("failed to parse template `{}`", .path.display())]
+    Parse {
+        path: PathBuf,
+        source: Box<ParseError>,
+    },
+    #[error("template `{}` is named `{name}` like another template", .path.display())]
+    DuplicateName { name: String, path: PathBuf },
+    #[error("alias `{alias}` refers to missing template `{name}`")]
+    MissingAliasTarget { alias: String, name: String },
+    #[error("failed to load {} templates:{}", .0.len(), DisplayErrors(.0))]
+    Multiple(Vec<TemplateLoadError>),
+}
+
+struct DisplayErrors<'a>(&'a [TemplateLoadError]);
+
+impl fmt::Display for DisplayErrors<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for err in self.0 {
+            write!(f, "\n\n{}", err)?;
+            let mut source = std::error::Error::source(err);
+            while let Some(err) = source {
+                write!(f, ":\n{}", err)?;
+                source = err.source();
+            }
+        }
+        Ok(())
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::reflect_render::Unescaped;
+    use std::fs;
+
+    use crate::{
+        loader::{LayeredLoader, MemoryLoader},
+        reflect_render::Unescaped,
+    };
 
     use super::*;
 
-    use bevy_reflect::{FromReflect, Reflect};
+    use bevy_reflect::{FromReflect, Reflect, ReflectRef};
     use pretty_assertions::assert_eq;
+    use templet_parser::Position;
 
     #[derive(Reflect)]
     struct Page {
@@ -117,7 +367,7 @@ mod tests {
     fn compile_templates(sources: Vec<(&'static str, &'static str)>) -> Templates {
         let mut map = HashMap::new();
         for (name, src) in sources.iter() {
-            map.insert(name.to_string(), Template::parse(src.to_string()));
+            map.insert(name.to_string(), Template::parse(src.to_string()).unwrap());
         }
         Templates::new(map)
     }
@@ -159,9 +409,87 @@ mod tests {
         );
     }
 
+    #[test]
+    fn context_stack() {
+        let templates = compile_templates(vec![(
+            "main",
+            "{{#items}}<li>{{name}} {{head.title}}{{#user}}{{#Customer}} {{name}}{{/Customer}}{{/user}}</li>{{/items}}",
+        )]);
+
+        let str = templates
+            .render_to_string(
+                "main",
+                &Page {
+                    head: Head {
+                        title: "Products".into(),
+                        css: vec![],
+                    },
+                    items: vec![Item {
+                        name: "Bread".into(),
+                    }],
+                    user: User::Customer {
+                        name: "Jane".into(),
+                    },
+                },
+            )
+            .unwrap();
+        assert_eq!(&str, "<li>Bread Products Jane</li>");
+    }
+
+    #[test]
+    fn context_stack_resolves_first_field_only() {
+        let templates =
+            compile_templates(vec![("main", "{{#second}}[{{second.third}}]{{/second}}")]);
+        let mut strict =
+            compile_templates(vec![("main", "{{#second}}{{third.second}}{{/second}}")]);
+        strict.set_strict(true);
+
+        let data = First {
+            second: Some(Second { third: Some(1) }),
+        };
+        assert_eq!(templates.render_to_string("main", &data).unwrap(), "[1]");
+        assert!(matches!(
+            strict.render_to_string("main", &data),
+            Err(RenderError::MissingPath { .. })
+        ));
+    }
+
+    #[derive(Reflect)]
+    struct Category {
+        name: String,
+        items: Vec<Item>,
+    }
+
+    #[test]
+    fn parent_and_root_access() {
+        let templates = compile_templates(vec![(
+            "main",
+            "{{#items}}{{name}}/{{../name}}/{{@root.name}} {{/items}}[{{../name}}]",
+        )]);
+
+        let str = templates
+            .render_to_string(
+                "main",
+                &Category {
+                    name: "Food".into(),
+                    items: vec![
+                        Item {
+                            name: "Bread".into(),
+                        },
+                        Item {
+                            name: "Milk".into(),
+                        },
+                    ],
+                },
+            )
+            .unwrap();
+        assert_eq!(&str, "Bread/Food/Food Milk/Food/Food []");
+    }
+
     #[test]
     fn load_dir() {
         let templates = Templates::load_dir("./templates", &["html"]).unwrap();
+        assert_eq!(templates.validate(), Ok(()));
         let str = templates
             .render_to_string(
                 "index.html",
@@ -188,27 +516,19 @@ mod tests {
 <!DOCTYPE html>
 <html>
   <head>
-  
-  <link rel="stylesheet" href="&#x2f;index.css" />
-  
-  <link rel="stylesheet" href="&#x2f;main.css" />
-  
-  <title>Products</title>
-</head>
-
+    <link rel="stylesheet" href="&#x2f;index.css" />
+    <link rel="stylesheet" href="&#x2f;main.css" />
+    <title>Products</title>
+  </head>
   <body>
     <h1>Products</h1>
     <ul>
-      
       <li>Bread</li>
-      
       <li>Milk</li>
-      
     </ul>
     <div class="footer">
-  <a href="/">Home</a>
-</div>
-
+      <a href="/">Home</a>
+    </div>
   </body>
 </html>
 "#
@@ -216,6 +536,134 @@ mod tests {
         assert_eq!(&str, expected);
     }
 
+    #[test]
+    fn load_dir_collect_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("partials")).unwrap();
+        fs::write(dir.path().join("index.html"), "{{> \"partials/ok.html\"}}").unwrap();
+        fs::write(dir.path().join("partials/ok.html"), "{{title}}").unwrap();
+        fs::write(dir.path().join("partials/unclosed.html"), "{{#items}}").unwrap();
+        fs::write(dir.path().join("mismatched.html"), "{{#a}}{{/b}}").unwrap();
+        fs::write(dir.path().join("binary.html"), [0xff, 0xfe]).unwrap();
+        fs::write(dir.path().join("partials/ok.txt"), "{{title}}").unwrap();
+
+        let fail_fast = Templates::load_dir(dir.path(), &["html"]);
+        let collected = Templates::load_dir_with(
+            dir.path(),
+            &LoadOptions::new(&["html", "txt"])
+                .strip_extensions(true)
+                .collect_errors(true),
+        );
+
+        assert!(matches!(
+            fail_fast,
+            Err(TemplateLoadError::Io { .. } | TemplateLoadError::Parse { .. })
+        ));
+        let errors = match collected {
+            Err(TemplateLoadError::Multiple(errors)) => errors,
+            _ => panic!("expected aggregated load errors"),
+        };
+        let mut failed: Vec<_> = errors
+            .iter()
+            .map(|err| match err {
+                TemplateLoadError::Io { path, .. } | TemplateLoadError::Parse { path, .. } => {
+                    path.strip_prefix(dir.path()).unwrap().to_owned()
+                }
+                TemplateLoadError::DuplicateName { name, .. } => PathBuf::from(name),
+                err => panic!("unexpected error: {}", err),
+            })
+            .collect();
+        failed.sort();
+        assert_eq!(
+            failed,
+            vec![
+                PathBuf::from("binary.html"),
+                PathBuf::from("mismatched.html"),
+                PathBuf::from("partials/ok"),
+                PathBuf::from("partials/unclosed.html"),
+            ]
+        );
+    }
+
+    #[test]
+    fn load_from_layers() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("partials")).unwrap();
+        fs::write(dir.path().join("partials/header.html"), "theme header").unwrap();
+
+        let mut defaults = MemoryLoader::default();
+        defaults.insert("index.html", "{{> \"partials/header.html\"}}, body");
+        defaults.insert("partials/header.html", "default header");
+        let options = LoadOptions::new(&["html"]);
+        let layered = LayeredLoader::new()
+            .layer(DirLoader::new(dir.path(), options.clone()))
+            .layer(defaults.clone());
+        let themed = Templates::load_from(&layered, &options);
+        let stripped = DirLoader::new(dir.path(), options.clone().strip_extensions(true));
+        let header = stripped.load("partials/header").unwrap();
+        let outside = stripped.load("../partials/header").unwrap();
+        let absolute = stripped
+            .load(&format!("{}/partials/header", dir.path().display()))
+            .unwrap();
+
+        let themed = themed.unwrap();
+        let src = themed.render_to_string("index.html", &()).unwrap();
+        assert_eq!(src, "theme header, body");
+        let defaults = Templates::load_from(&defaults, &options).unwrap();
+        let src = defaults.render_to_string("index.html", &()).unwrap();
+        assert_eq!(src, "default header, body");
+
+        assert_eq!(header.unwrap().source, "theme header");
+        assert!(outside.is_none());
+        assert!(absolute.is_none());
+    }
+
+    #[test]
+    fn load_dir_naming() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("pages")).unwrap();
+        fs::write(
+            dir.path().join("pages/index.html"),
+            "{{> \"site/nav\"}} index",
+        )
+        .unwrap();
+        fs::write(dir.path().join("nav.html"), "nav").unwrap();
+        fs::write(dir.path().join("nav.txt"), "nav").unwrap();
+
+        let options = LoadOptions::new(&["html"])
+            .strip_extensions(true)
+            .prefix("site")
+            .alias("home", "site/pages/index");
+        let templates = Templates::load_dir_with(dir.path(), &options);
+        let duplicate = Templates::load_dir_with(
+            dir.path(),
+            &LoadOptions::new(&["html", "txt"]).strip_extensions(true),
+        );
+        let missing_alias = Templates::load_dir_with(
+            dir.path(),
+            &LoadOptions::new(&["html"]).alias("home", "index"),
+        );
+
+        let templates = templates.unwrap();
+        let mut names: Vec<_> = templates.iter().map(|(name, _)| name).collect();
+        names.sort();
+        assert_eq!(names, vec!["site/nav", "site/pages/index"]);
+        assert_eq!(
+            templates.render_to_string("home", &()).unwrap(),
+            "nav index"
+        );
+        assert_eq!(templates.validate(), Ok(()));
+
+        assert!(matches!(
+            duplicate,
+            Err(TemplateLoadError::DuplicateName { ref name, .. }) if name == "nav"
+        ));
+        assert!(matches!(
+            missing_alias,
+            Err(TemplateLoadError::MissingAliasTarget { ref alias, .. }) if alias == "home"
+        ));
+    }
+
     #[test]
     fn test_enum_sections() {
         let templates = compile_templates(vec![(
@@ -308,6 +756,121 @@ mod tests {
         assert_eq!(src, "(Exists: 12)");
     }
 
+    fn compile_strict_templates(sources: Vec<(&'static str, &'static str)>) -> Templates {
+        let mut templates = compile_templates(sources);
+        templates.set_strict(true);
+        templates
+    }
+
+    #[test]
+    fn strict_missing_path() {
+        let templates =
+            compile_strict_templates(vec![("main", "{{#second}}\n  {{thrid}}\n{{/second}}")]);
+
+        let err = templates
+            .render_to_string(
+                "main",
+                &First {
+                    second: Some(Second { third: Some(12) }),
+                },
+            )
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            RenderError::MissingPath { ref template, ref path, position }
+                if template == "main" && path == "thrid" && position.line == 2 && position.column == 3
+        ));
+        assert_eq!(
+            err.to_string(),
+            "`thrid` not found in template `main` at 2:3"
+        );
+    }
+
+    #[test]
+    fn strict_allows_absent_values() {
+        let templates = compile_strict_templates(vec![(
+            "main",
+            "{{second.third}}{{#second.third}}{{.}}{{/second.third}}{{^second}}none{{/second}}",
+        )]);
+
+        let src = templates
+            .render_to_string("main", &First { second: None })
+            .unwrap();
+        assert_eq!(src, "none");
+    }
+
+    #[test]
+    fn strict_missing_template_and_include() {
+        let templates = compile_strict_templates(vec![("main", "a {{> \"missing\"}}")]);
+
+        let err = templates.render_to_string("nope", &()).unwrap_err();
+        assert!(matches!(err, RenderError::MissingTemplate { ref name } if name == "nope"));
+
+        let err = templates.render_to_string("main", &()).unwrap_err();
+        assert!(matches!(
+            err,
+            RenderError::MissingInclude { ref template, ref name, .. }
+                if template == "main" && name == "missing"
+        ));
+    }
+
+    #[test]
+    fn strict_unsupported_value() {
+        let templates = compile_strict_templates(vec![("main", "{{second}}")]);
+
+        let err = templates
+            .render_to_string(
+                "main",
+                &First {
+                    second: Some(Second { third: None }),
+                },
+            )
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            RenderError::UnsupportedValue { ref path, ref type_name, .. }
+                if path == "second" && type_name.ends_with("Second")
+        ));
+    }
+
+    #[test]
+    fn include_cycle() {
+        let templates = compile_templates(vec![
+            ("main", r#"{{#second}}{{> "a"}}{{/second}}"#),
+            ("a", r#"a {{> "b"}}"#),
+            ("b", r#"b {{> "a"}}"#),
+        ]);
+
+        let err = templates
+            .render_to_string(
+                "main",
+                &First {
+                    second: Some(Second { third: None }),
+                },
+            )
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "include cycle a -> b -> a in template `b` at 1:3"
+        );
+    }
+
+    #[test]
+    fn include_depth_limit() {
+        let mut map = HashMap::new();
+        for i in 0..100 {
+            let src = format!(r#"{{{{> "{}"}}}}"#, i + 1);
+            map.insert(i.to_string(), Template::parse(src).unwrap());
+        }
+        let templates = Templates::new(map);
+
+        let err = templates.render_to_string("0", &()).unwrap_err();
+        assert!(matches!(
+            err,
+            RenderError::LimitExceeded { ref template, limit: 64, .. } if template == "64"
+        ));
+    }
+
     #[derive(Reflect)]
     struct BoolSection {
         section: bool,
@@ -453,6 +1016,351 @@ mod tests {
         assert_eq!(src, "<div>FOOBAR</div>");
     }
 
+    #[test]
+    fn render_triple_mustache_unescaped() {
+        let templates = compile_templates(vec![("main", "{{{.}}} {{& .}} {{.}}")]);
+        let src = templates
+            .render_to_string("main", &"<b>".to_owned())
+            .unwrap();
+        assert_eq!(src, "<b> <b> &lt;b&gt;");
+    }
+
+    #[test]
+    fn indented_partials() {
+        let templates = compile_templates(vec![
+            ("main", "a\n  {{> \"outer\"}}\nb\n"),
+            ("outer", "{{{.}}}\n  {{> \"inner\"}}\n"),
+            ("inner", "x\ny\n"),
+        ]);
+        let src = templates
+            .render_to_string("main", &"1\n2".to_owned())
+            .unwrap();
+        assert_eq!(src, "a\n  1\n2\n    x\n    y\nb\n");
+    }
+
+    #[test]
+    fn inheritance() {
+        let templates = compile_templates(vec![
+            (
+                "base",
+                "<title>{{$title}}Site{{/title}}</title>\n{{$body}}{{/body}}\n",
+            ),
+            (
+                "layout",
+                "{{< \"base\"}}\n{{$body}}<main>{{$content}}Empty{{/content}}</main>{{/body}}\n{{/\"base\"}}\n",
+            ),
+            (
+                "page",
+                "{{< \"layout\"}}\n{{$title}}{{head.title}}{{/title}}\n{{$content}}{{#items}}{{name}} {{/items}}{{/content}}\n{{/\"layout\"}}\n",
+            ),
+        ]);
+        let page = Page {
+            user: User::Admin,
+            head: Head {
+                title: "Products".into(),
+                css: vec![],
+            },
+            items: vec![Item {
+                name: "Bread".into(),
+            }],
+        };
+
+        let src = templates.render_to_string("page", &page).unwrap();
+        assert_eq!(src, "<title>Products</title>\n<main>Bread </main>\n");
+        let src = templates.render_to_string("layout", &page).unwrap();
+        assert_eq!(src, "<title>Site</title>\n<main>Empty</main>\n");
+    }
+
+    #[test]
+    fn parent_reentered_with_other_blocks() {
+        let templates = compile_templates(vec![
+            ("main", "{{< \"parent\"}}{{$foo}}override{{/foo}}{{/\"parent\"}}"),
+            (
+                "parent",
+                "{{$foo}}default content{{/foo}} {{$bar}}{{< \"parent2\"}}{{/\"parent2\"}}{{/bar}}",
+            ),
+            (
+                "parent2",
+                "{{$foo}}parent2 default content{{/foo}} {{< \"parent\"}}{{$bar}}don't recurse{{/bar}}{{/\"parent\"}}",
+            ),
+            ("loop", "{{< \"loop\"}}{{$foo}}again{{/foo}}{{/\"loop\"}}"),
+        ]);
+
+        let src = templates.render_to_string("main", &()).unwrap();
+        assert_eq!(src, "override override override don't recurse");
+
+        let err = templates.render_to_string("loop", &()).unwrap_err();
+        assert!(matches!(err, RenderError::IncludeCycle { .. }));
+    }
+
+    #[test]
+    fn inline_partials() {
+        let templates = compile_strict_templates(vec![
+            (
+                "main",
+                "{{#*inline \"item\"}}\n<li>{{name}}</li>\n{{/inline}}\n<ul>\n{{#items}}\n  {{> \"item\"}}\n{{/items}}\n</ul>\n",
+            ),
+            ("page", "{{< \"list\"}}{{#*inline \"item\"}}{{name}};{{/inline}}{{/\"list\"}}"),
+            ("list", "{{#items}}{{> \"item\"}}{{/items}}"),
+            ("other", "{{> \"item\"}}"),
+        ]);
+        let page = Page {
+            user: User::Admin,
+            head: Head {
+                title: "Products".into(),
+                css: vec![],
+            },
+            items: vec![
+                Item {
+                    name: "Bread".into(),
+                },
+                Item {
+                    name: "Milk".into(),
+                },
+            ],
+        };
+
+        let src = templates.render_to_string("main", &page).unwrap();
+        assert_eq!(src, "<ul>\n  <li>Bread</li>\n  <li>Milk</li>\n</ul>\n");
+        let src = templates.render_to_string("page", &page).unwrap();
+        assert_eq!(src, "Bread;Milk;");
+
+        let err = templates.render_to_string("other", &page).unwrap_err();
+        assert!(matches!(err, RenderError::MissingInclude { ref name, .. } if name == "item"));
+    }
+
+    #[test]
+    fn validate() {
+        let templates = compile_templates(vec![
+            (
+                "main",
+                "{{> \"header\"}}{{#items}}{{> \"main\"}}{{/items}}{{> *name}}",
+            ),
+            ("header", "{{> \"nav\"}}{{> \"row\"}}"),
+            ("list", "{{#*inline \"row\"}}{{/inline}}{{> \"header\"}}"),
+        ]);
+        assert_eq!(
+            templates.validate(),
+            Err(vec![ValidationError::MissingInclude {
+                template: "header".into(),
+                name: "nav".into(),
+                position: Position { line: 1, column: 1 },
+            }])
+        );
+
+        let templates = compile_templates(vec![
+            ("a", "{{> \"b\"}}"),
+            ("b", "\n{{< \"c\"}}{{/\"c\"}}"),
+            ("c", "{{#x}}{{> \"c\"}}{{/x}}{{> \"a\" x}}{{> \"a\" y=x}}"),
+        ]);
+        assert_eq!(
+            templates.validate(),
+            Err(vec![ValidationError::IncludeCycle {
+                template: "c".into(),
+                chain: vec!["a".into(), "b".into(), "c".into(), "a".into()],
+                position: Position {
+                    line: 1,
+                    column: 33
+                },
+            }])
+        );
+    }
+
+    #[test]
+    fn relative_partials() {
+        let templates = compile_strict_templates(vec![
+            (
+                "pages/index.html",
+                "{{> \"./parts/nav.html\"}}|{{> \"shared/footer.html\"}}",
+            ),
+            (
+                "pages/parts/nav.html",
+                "nav {{> \"../../shared/logo.html\"}}",
+            ),
+            ("shared/logo.html", "logo"),
+            ("shared/footer.html", "footer {{> \"./logo.html\"}}"),
+            ("pages/escape.html", "{{> \"../../logo.html\"}}"),
+            (
+                "pages/dotted.html",
+                "{{> \"shared/./logo.html\"}} {{> \"pages/../shared/logo.html\"}}",
+            ),
+            ("pages/above.html", "{{> \"shared/../../logo.html\"}}"),
+        ]);
+        let src = templates.render_to_string("pages/index.html", &()).unwrap();
+        assert_eq!(src, "nav logo|footer logo");
+        let src = templates
+            .render_to_string("pages/dotted.html", &())
+            .unwrap();
+        assert_eq!(src, "logo logo");
+
+        let err = templates
+            .render_to_string("pages/escape.html", &())
+            .unwrap_err();
+        assert!(
+            matches!(err, RenderError::IncludeOutsideRoot { ref name, .. } if name == "../../logo.html")
+        );
+        assert_eq!(
+            templates.validate(),
+            Err(vec![
+                ValidationError::IncludeOutsideRoot {
+                    template: "pages/above.html".into(),
+                    name: "shared/../../logo.html".into(),
+                    position: Position { line: 1, column: 1 },
+                },
+                ValidationError::IncludeOutsideRoot {
+                    template: "pages/escape.html".into(),
+                    name: "../../logo.html".into(),
+                    position: Position { line: 1, column: 1 },
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn relative_partials_in_inherited_parts() {
+        let templates = compile_strict_templates(vec![
+            (
+                "pages/page.html",
+                "{{< \"layouts/base.html\"}}{{#*inline \"nav\"}}{{> \"./nav.html\"}}{{/inline}}{{$body}}{{> \"./body.html\"}}{{/body}}{{/\"layouts/base.html\"}}",
+            ),
+            ("pages/nav.html", "page nav"),
+            ("pages/body.html", "page body"),
+            ("layouts/base.html", "{{> \"nav\"}}, {{$body}}{{/body}}"),
+        ]);
+        let src = templates.render_to_string("pages/page.html", &()).unwrap();
+        assert_eq!(src, "page nav, page body");
+    }
+
+    #[derive(Reflect)]
+    struct TreeNode {
+        name: String,
+        children: Vec<TreeNode>,
+    }
+
+    // The derived impl overflows on recursive types.
+    impl FromReflect for TreeNode {
+        fn from_reflect(reflect: &dyn Reflect) -> Option<Self> {
+            match reflect.reflect_ref() {
+                ReflectRef::Struct(st) => Some(TreeNode {
+                    name: String::from_reflect(st.field("name")?)?,
+                    children: Vec::from_reflect(st.field("children")?)?,
+                }),
+                _ => None,
+            }
+        }
+    }
+
+    fn node(name: &str, children: Vec<TreeNode>) -> TreeNode {
+        TreeNode {
+            name: name.into(),
+            children,
+        }
+    }
+
+    #[test]
+    fn recursive_partials() {
+        let mut templates = compile_strict_templates(vec![(
+            "tree",
+            "{{name}}{{#children}}[{{> \"tree\"}}]{{/children}}",
+        )]);
+        let tree = node(
+            "root",
+            vec![
+                node("a", vec![node("a1", vec![]), node("a2", vec![])]),
+                node("b", vec![node("b1", vec![node("b11", vec![])])]),
+            ],
+        );
+        let src = templates.render_to_string("tree", &tree).unwrap();
+        assert_eq!(src, "root[a[a1][a2]][b[b1[b11]]]");
+
+        templates.set_max_include_depth(2);
+        let err = templates.render_to_string("tree", &tree).unwrap_err();
+        assert!(matches!(err, RenderError::LimitExceeded { limit: 2, .. }));
+        let src = templates
+            .render_to_string("tree", &tree.children[0])
+            .unwrap();
+        assert_eq!(src, "a[a1][a2]");
+    }
+
+    #[derive(Reflect)]
+    struct Dashboard {
+        widgets: Vec<Widget>,
+    }
+
+    #[derive(Reflect, FromReflect)]
+    struct Widget {
+        template: String,
+        title: String,
+    }
+
+    #[test]
+    fn dynamic_partials() {
+        let templates = compile_strict_templates(vec![
+            ("main", "{{#widgets}}{{> *template}}{{/widgets}}"),
+            ("chart", "<chart>{{title}}</chart>"),
+            ("table", "<table>{{title}}</table>"),
+        ]);
+        let dashboard = Dashboard {
+            widgets: vec![
+                Widget {
+                    template: "chart".into(),
+                    title: "Sales".into(),
+                },
+                Widget {
+                    template: "table".into(),
+                    title: "Orders".into(),
+                },
+            ],
+        };
+        let src = templates.render_to_string("main", &dashboard).unwrap();
+        assert_eq!(src, "<chart>Sales</chart><table>Orders</table>");
+
+        let templates = compile_strict_templates(vec![("main", "{{> *title}}")]);
+        let err = templates
+            .render_to_string("main", &dashboard.widgets[0])
+            .unwrap_err();
+        assert!(matches!(err, RenderError::MissingInclude { ref name, .. } if name == "Sales"));
+    }
+
+    #[test]
+    fn partial_arguments() {
+        let templates = compile_strict_templates(vec![
+            (
+                "main",
+                "{{#widgets}}{{> \"widget\" . heading=title}}{{/widgets}}",
+            ),
+            ("widget", "<h1>{{heading}}</h1>{{template}}"),
+            ("first", "{{> \"widget\" widgets[0]}}"),
+        ]);
+        let dashboard = Dashboard {
+            widgets: vec![
+                Widget {
+                    template: "chart".into(),
+                    title: "Sales".into(),
+                },
+                Widget {
+                    template: "table".into(),
+                    title: "Orders".into(),
+                },
+            ],
+        };
+        let src = templates.render_to_string("main", &dashboard).unwrap();
+        assert_eq!(src, "<h1>Sales</h1>chart<h1>Orders</h1>table");
+
+        let err = templates.render_to_string("first", &dashboard).unwrap_err();
+        assert!(matches!(err, RenderError::MissingPath { ref path, .. } if path == "heading"));
+
+        let templates = compile_strict_templates(vec![
+            (
+                "main",
+                "{{#widgets}}{{> \"title\" title=template}}{{/widgets}}",
+            ),
+            ("title", "{{title}}:{{#.}}{{title}}{{/.}};"),
+        ]);
+        let src = templates.render_to_string("main", &dashboard).unwrap();
+        assert_eq!(src, "chart:Sales;table:Orders;");
+    }
+
     #[derive(Reflect)]
     struct UnitStruct(i32);
 
@@ -0,0 +1,318 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
+use syn::{
+    bracketed, parenthesized,
+    parse::{Parse, ParseStream},
+    parse_macro_input,
+    punctuated::Punctuated,
+    Ident, LitBool, LitStr, Token,
+};
+use templet_parser::{parse, parse::Part, template_name, validate, ParseOptions};
+
+/// Embeds the templates of a directory into the binary, returning
+/// `Result<templet::Templates, templet::TemplateLoadError>`. Use it through
+/// `templet::include_templates!`, enabled by templet's `embed` feature.
+///
+/// The path is relative to the crate's `Cargo.toml`. The remaining
+/// arguments mirror `templet::LoadOptions` and `templet::ParseOptions`, and
+/// all of them are optional:
+///
+/// ```ignore
+/// let templates = templet::include_templates!(
+///     "templates",
+///     extensions = ["html"],
+///     strip_extensions = true,
+///     prefix = "site",
+///     alias = ("home", "site/index"),
+///     delimiters = ("<%", "%>"),
+///     legacy_comments = true,
+///     check = true,
+/// )
+/// .unwrap();
+/// ```
+///
+/// Without `extensions`, every file of the directory is embedded. Templates
+/// are named like `Templates::load_dir_with` names them, and are parsed and
+/// their includes validated at compile time, unless disabled with
+/// `check = false`.
+///
+/// Changes to embedded files are picked up by the next build, but added
+/// files are only found once the crate calling the macro is rebuilt.
+#[proc_macro]
+pub fn include_templates(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let args = parse_macro_input!(input as Args);
+    let manifest_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap_or_default();
+    let dir = Path::new(&manifest_dir).join(args.dir.value());
+    match embed(&dir, &args.options) {
+        Ok(tokens) => tokens.into(),
+        Err(message) => syn::Error::new(args.dir.span(), message)
+            .to_compile_error()
+            .into(),
+    }
+}
+
+struct Args {
+    dir: LitStr,
+    options: Options,
+}
+
+struct Options {
+    extensions: Option<Vec<String>>,
+    strip_extensions: bool,
+    prefix: Option<String>,
+    aliases: Vec<(String, String)>,
+    delimiters: Option<(String, String)>,
+    legacy_comments: bool,
+    check: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            extensions: None,
+            strip_extensions: false,
+            prefix: None,
+            aliases: Vec::new(),
+            delimiters: None,
+            legacy_comments: false,
+            check: true,
+        }
+    }
+}
+
+impl Options {
+    fn parse_options(&self) -> ParseOptions {
+        let options = ParseOptions::new().legacy_comments(self.legacy_comments);
+        match &self.delimiters {
+            Some((open, close)) => options.delimiters(open, close),
+            None => options,
+        }
+    }
+}
+
+impl Parse for Args {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let dir = input.parse()?;
+        let mut options = Options::default();
+        while input.parse::<Option<Token![,]>>()?.is_some() && !input.is_empty() {
+            let name: Ident = input.parse()?;
+            input.parse::<Token![=]>()?;
+            match name.to_string().as_str() {
+                "extensions" => {
+                    let content;
+                    bracketed!(content in input);
+                    let extensions = Punctuated::<LitStr, Token![,]>::parse_terminated(&content)?;
+                    options.extensions = Some(extensions.iter().map(LitStr::value).collect());
+                }
+                "strip_extensions" => options.strip_extensions = input.parse::<LitBool>()?.value,
+                "prefix" => options.prefix = Some(input.parse::<LitStr>()?.value()),
+                "alias" => options.aliases.push(pair(input)?),
+                "delimiters" => options.delimiters = Some(pair(input)?),
+                "legacy_comments" => options.legacy_comments = input.parse::<LitBool>()?.value,
+                "check" => options.check = input.parse::<LitBool>()?.value,
+                _ => return Err(syn::Error::new(name.span(), "unknown option")),
+            }
+        }
+        Ok(Args { dir, options })
+    }
+}
+
+/// Parses a `("a", "b")` pair of strings.
+fn pair(input: ParseStream) -> syn::Result<(String, String)> {
+    let content;
+    parenthesized!(content in input);
+    let first: LitStr = content.parse()?;
+    content.parse::<Token![,]>()?;
+    let second: LitStr = content.parse()?;
+    content.parse::<Option<Token![,]>>()?;
+    Ok((first.value(), second.value()))
+}
+
+struct File {
+    name: String,
+    path: PathBuf,
+    source: String,
+}
+
+fn embed(dir: &Path, options: &Options) -> Result<TokenStream, String> {
+    let files = files(dir, options)?;
+    if options.check {
+        check_templates(&files, options)?;
+    }
+
+    let names = files.iter().map(|file| &file.name);
+    let paths = files
+        .iter()
+        .map(|file| LitStr::new(&file.path.to_string_lossy(), Span::call_site()));
+    let legacy_comments = options.legacy_comments;
+    let delimiters = options
+        .delimiters
+        .iter()
+        .map(|(open, close)| quote!(.delimiters(#open, #close)));
+    let aliases = options
+        .aliases
+        .iter()
+        .map(|(alias, name)| quote!(.alias(#alias, #name)));
+    Ok(quote! {
+        {
+            let mut loader = ::templet::MemoryLoader::default();
+            #( loader.insert(#names, include_str!(#paths)); )*
+            let parse_options = ::templet::ParseOptions::new()
+                .legacy_comments(#legacy_comments)
+                #(#delimiters)*;
+            let options = ::templet::LoadOptions::new(&[])
+                .parse_options(parse_options)
+                #(#aliases)*;
+            ::templet::Templates::load_from(&loader, &options)
+        }
+    })
+}
+
+/// Every template file in `dir`, sorted by name.
+fn files(dir: &Path, options: &Options) -> Result<Vec<File>, String> {
+    let mut files = Vec::new();
+    let mut names = HashSet::new();
+    for entry in walkdir::WalkDir::new(dir).sort_by_file_name() {
+        let entry = entry.map_err(|err| format!("could not walk `{}`: {}", dir.display(), err))?;
+        let path = entry.path();
+        if !entry.file_type().is_file() || !has_extension(path, options) {
+            continue;
+        }
+        let relative = path.strip_prefix(dir).map_err(|err| err.to_string())?;
+        let name = template_name(
+            relative,
+            options.strip_extensions,
+            options.prefix.as_deref(),
+        );
+        if !names.insert(name.clone()) {
+            return Err(format!(
+                "template `{}` is named `{}` like another template",
+                path.display(),
+                name
+            ));
+        }
+        let source = fs::read_to_string(path)
+            .map_err(|err| format!("failed to read template `{}`: {}", path.display(), err))?;
+        files.push(File {
+            name,
+            path: path.to_owned(),
+            source,
+        });
+    }
+    files.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(files)
+}
+
+fn has_extension(path: &Path, options: &Options) -> bool {
+    match (&options.extensions, path.extension()) {
+        (None, _) => true,
+        (Some(extensions), Some(ext)) => extensions.iter().any(|s| ext == s.as_str()),
+        (Some(_), None) => false,
+    }
+}
+
+fn check_templates(files: &[File], options: &Options) -> Result<(), String> {
+    let parse_options = options.parse_options();
+    let mut parsed = Vec::with_capacity(files.len());
+    for file in files {
+        let parts = parse(&file.source, &parse_options).map_err(|err| {
+            format!(
+                "failed to parse template `{}`:\n{}",
+                file.path.display(),
+                err
+            )
+        })?;
+        parsed.push((file.name.as_str(), parts));
+    }
+    let templates: BTreeMap<&str, &[Part]> = parsed
+        .iter()
+        .map(|(name, parts)| (*name, parts.as_slice()))
+        .collect();
+
+    let aliases: HashMap<String, String> = options.aliases.iter().cloned().collect();
+    for (alias, name) in &options.aliases {
+        if !templates.contains_key(name.as_str()) {
+            return Err(format!(
+                "alias `{}` refers to missing template `{}`",
+                alias, name
+            ));
+        }
+    }
+    validate(&templates, &aliases).map_err(|errors| {
+        let errors: Vec<String> = errors.iter().map(|err| err.to_string()).collect();
+        format!("invalid templates:\n{}", errors.join("\n"))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checks_templates() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("partials")).unwrap();
+        fs::write(dir.path().join("index.html"), "{{> \"partials/nav.html\"}}").unwrap();
+        fs::write(dir.path().join("partials/nav.html"), "nav").unwrap();
+        let valid = embed(dir.path(), &Options::default()).map(|_| ());
+        fs::write(dir.path().join("partials/nav.html"), "{{#unclosed}}").unwrap();
+        let unchecked = Options {
+            check: false,
+            ..Options::default()
+        };
+        let unchecked = embed(dir.path(), &unchecked).map(|_| ());
+        let invalid = embed(dir.path(), &Options::default());
+        fs::remove_file(dir.path().join("partials/nav.html")).unwrap();
+        let missing = embed(dir.path(), &Options::default());
+
+        assert_eq!(valid, Ok(()));
+        assert_eq!(unchecked, Ok(()));
+        assert!(invalid.unwrap_err().contains("was never closed"));
+        assert!(missing
+            .unwrap_err()
+            .contains("included template `partials/nav.html` not found"));
+    }
+
+    #[test]
+    fn load_options() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("pages")).unwrap();
+        fs::write(
+            dir.path().join("pages/index.html"),
+            "<%> \"site/nav\"%> \\<% c %>",
+        )
+        .unwrap();
+        fs::write(dir.path().join("nav.html"), "nav").unwrap();
+        fs::write(dir.path().join("notes.txt"), "{{#unclosed}}").unwrap();
+
+        let Args { options, .. } = syn::parse_str::<Args>(
+            r#""templates", extensions = ["html"], strip_extensions = true, prefix = "site",
+            alias = ("home", "site/pages/index"), delimiters = ("<%", "%>"),
+            legacy_comments = true"#,
+        )
+        .unwrap();
+        let names: Vec<String> = files(dir.path(), &options)
+            .unwrap()
+            .into_iter()
+            .map(|file| file.name)
+            .collect();
+        let valid = embed(dir.path(), &options).map(|_| ());
+        let missing_alias = Options {
+            aliases: vec![("home".into(), "index".into())],
+            ..options
+        };
+        let missing_alias = embed(dir.path(), &missing_alias);
+
+        assert_eq!(names, vec!["site/nav", "site/pages/index"]);
+        assert_eq!(valid, Ok(()));
+        assert!(missing_alias
+            .unwrap_err()
+            .contains("alias `home` refers to missing template `index`"));
+    }
+}
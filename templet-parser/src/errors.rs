@@ -0,0 +1,170 @@
+use std::fmt;
+
+use thiserror::Error;
+
+/// Location of a tag in a template source.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Position {
+    pub line: u32,
+    pub column: usize,
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+/// Problem with the includes of a set of templates, found by
+/// `Templates::validate`.
+#[derive(Clone, Debug, PartialEq, Eq, Error)]
+pub enum ValidationError {
+    #[error("included template `{name}` not found in template `{template}` at {position}")]
+    MissingInclude {
+        template: String,
+        name: String,
+        position: Position,
+    },
+    #[error("include cycle {} in template `{template}` at {position}", .chain.join(" -> "))]
+    IncludeCycle {
+        template: String,
+        chain: Vec<String>,
+        position: Position,
+    },
+    #[error("included template `{name}` is outside the template root in template `{template}` at {position}")]
+    IncludeOutsideRoot {
+        template: String,
+        name: String,
+        position: Position,
+    },
+}
+
+/// Error returned when a template source cannot be parsed.
+///
+/// The `Display` implementation renders a caret diagnostic pointing at the
+/// offending source.
+#[derive(Clone, Debug, PartialEq, Eq, Error)]
+pub struct ParseError {
+    pub kind: Box<ParseErrorKind>,
+    /// 1-based line of the offending source.
+    pub line: u32,
+    /// 1-based column (in characters) of the offending source.
+    pub column: usize,
+    /// Number of characters of the offending source, starting at `column`.
+    pub width: usize,
+    /// The source line containing the error.
+    pub snippet: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    UnclosedTag {
+        expected: String,
+    },
+    InvalidAccess,
+    InvalidDelimiters,
+    ExpectedIncludePath,
+    UnterminatedIncludePath,
+    InvalidEscape,
+    InvalidSyntax,
+    UnclosedSection {
+        open: String,
+        expected: String,
+    },
+    MismatchedSection {
+        open: String,
+        open_line: u32,
+        open_column: usize,
+        close: String,
+        expected: String,
+    },
+    StrayClosingTag {
+        close: String,
+    },
+}
+
+impl ParseErrorKind {
+    fn hint(&self) -> String {
+        match self {
+            ParseErrorKind::UnclosedTag { expected }
+            | ParseErrorKind::UnclosedSection { expected, .. }
+            | ParseErrorKind::MismatchedSection { expected, .. } => {
+                format!("expected `{}`", expected)
+            }
+            ParseErrorKind::InvalidAccess => {
+                "expected `.`, a `VariantName` or a path like `field.0[1]`, `../field` or `@root.field`".into()
+            }
+            ParseErrorKind::ExpectedIncludePath => {
+                "expected a quoted path like `\"header.html\"` or `*` and a variable path".into()
+            }
+            ParseErrorKind::InvalidDelimiters => {
+                "expected two delimiters without whitespace or `=`, like `{{=<% %>=}}`".into()
+            }
+            ParseErrorKind::UnterminatedIncludePath => "expected a closing `\"`".into(),
+            ParseErrorKind::InvalidEscape => "only `\\\"` can be escaped in a path".into(),
+            ParseErrorKind::InvalidSyntax => "unexpected character".into(),
+            ParseErrorKind::StrayClosingTag { .. } => "no open section to close".into(),
+        }
+    }
+}
+
+impl fmt::Display for ParseErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseErrorKind::UnclosedTag { .. } => write!(f, "unclosed tag"),
+            ParseErrorKind::InvalidAccess => write!(f, "invalid variable path"),
+            ParseErrorKind::InvalidDelimiters => write!(f, "invalid delimiters"),
+            ParseErrorKind::ExpectedIncludePath => write!(f, "missing include path"),
+            ParseErrorKind::UnterminatedIncludePath => write!(f, "unterminated include path"),
+            ParseErrorKind::InvalidEscape => write!(f, "invalid escape in include path"),
+            ParseErrorKind::InvalidSyntax => write!(f, "invalid syntax"),
+            ParseErrorKind::UnclosedSection { open, .. } => {
+                write!(f, "section `{}` was never closed", open)
+            }
+            ParseErrorKind::MismatchedSection {
+                open,
+                open_line,
+                open_column,
+                close,
+                ..
+            } => write!(
+                f,
+                "section `{}` opened at {}:{} was closed by `{}`",
+                open, open_line, open_column, close
+            ),
+            ParseErrorKind::StrayClosingTag { close } => {
+                write!(f, "unexpected closing tag `{}`", close)
+            }
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let line_number = self.line.to_string();
+        let gutter = " ".repeat(line_number.len());
+        let indent: String = self
+            .snippet
+            .chars()
+            .take(self.column.saturating_sub(1))
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        let line_width = self.snippet.chars().count();
+        let width = self
+            .width
+            .min(line_width.saturating_sub(self.column - 1))
+            .max(1);
+
+        writeln!(f, "error: {} at {}:{}", self.kind, self.line, self.column)?;
+        writeln!(f, "{} |", gutter)?;
+        writeln!(f, "{} | {}", line_number, self.snippet)?;
+        write!(
+            f,
+            "{} | {}{} {}",
+            gutter,
+            indent,
+            "^".repeat(width),
+            self.kind.hint()
+        )
+    }
+}
@@ -0,0 +1,21 @@
+//! Template parser and include checks shared by `templet` and
+//! `templet-macros`. Use them through the `templet` crate.
+//!
+//! Only the items `templet` re-exports are stable. The template syntax tree
+//! and the functions working on it are internal to `templet` and may change
+//! in any release.
+
+mod errors;
+mod names;
+#[doc(hidden)]
+pub mod parse;
+mod validate;
+
+pub use errors::{ParseError, ParseErrorKind, Position, ValidationError};
+#[doc(hidden)]
+pub use names::{include_key, template_name};
+#[doc(hidden)]
+pub use parse::parse;
+pub use parse::ParseOptions;
+#[doc(hidden)]
+pub use validate::validate;
@@ -0,0 +1,66 @@
+use std::{borrow::Cow, path::Path};
+
+/// Resolves the name of an included template. Names starting with `./` or
+/// `../` are relative to the directory of the including template `from`,
+/// other names to the template root. `.` and `..` segments are resolved, and
+/// names leading outside the template root resolve to `None`.
+pub fn include_key<'n>(from: &str, name: &'n str) -> Option<Cow<'n, str>> {
+    let relative = name.starts_with("./") || name.starts_with("../");
+    if !relative
+        && !name
+            .split('/')
+            .any(|segment| segment == "." || segment == "..")
+    {
+        return Some(Cow::Borrowed(name));
+    }
+    let mut segments: Vec<&str> = Vec::new();
+    if relative {
+        segments.extend(from.split('/'));
+        segments.pop();
+    }
+    for segment in name.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop()?;
+            }
+            segment => segments.push(segment),
+        }
+    }
+    Some(Cow::Owned(segments.join("/")))
+}
+
+/// Name of the template at the relative `path`, with its extension removed
+/// if `strip_extensions` is set and `prefix` prepended. Path components are
+/// always separated by `/`.
+pub fn template_name(path: &Path, strip_extensions: bool, prefix: Option<&str>) -> String {
+    let path = match strip_extensions {
+        true => path.with_extension(""),
+        false => path.to_owned(),
+    };
+    let mut name = prefix.unwrap_or_default().to_owned();
+    for component in path.components() {
+        if !name.is_empty() && !name.ends_with('/') {
+            name.push('/');
+        }
+        name.push_str(&component.as_os_str().to_string_lossy());
+    }
+    name
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn include_keys() {
+        let key = |name| include_key("pages/index.html", name).map(Cow::into_owned);
+        assert_eq!(key("nav.html").unwrap(), "nav.html");
+        assert_eq!(key("./nav.html").unwrap(), "pages/nav.html");
+        assert_eq!(key("../shared/./nav.html").unwrap(), "shared/nav.html");
+        assert_eq!(key("partials/./nav.html").unwrap(), "partials/nav.html");
+        assert_eq!(key("shared/../nav.html").unwrap(), "nav.html");
+        assert_eq!(key("../../nav.html"), None);
+        assert_eq!(key("shared/../../nav.html"), None);
+    }
+}
@@ -0,0 +1,1309 @@
+use std::{fmt, mem};
+
+use nom::{
+    branch::alt,
+    bytes::complete::{is_not, tag, take_till1, take_until},
+    character::complete::{one_of, space0, space1},
+    combinator::{cond, not, opt, recognize},
+    error::ErrorKind,
+    multi::{fold_many1, many0, many0_count, many1_count},
+    sequence::{delimited, pair, preceded, separated_pair, terminated, tuple},
+    Err, IResult, InputTake,
+};
+use nom_locate::LocatedSpan;
+use nom_unicode::complete::{alpha1, alphanumeric1, upper1};
+
+use crate::errors::{ParseError, ParseErrorKind, Position};
+
+type Span<'a> = LocatedSpan<&'a str>;
+
+type Result<'a, T> = IResult<Span<'a>, T, Error<'a>>;
+
+#[derive(Debug, Eq, PartialEq)]
+pub enum Part<'a> {
+    Text(&'a str),
+    Variable(Access<'a>, Position),
+    UnescapedVariable(Access<'a>, Position),
+    Section(Access<'a>, Position, Vec<Part<'a>>),
+    InvertedSection(Access<'a>, Position, Vec<Part<'a>>),
+    Include(Include<'a>),
+    /// `{{$name}}default{{/name}}`, replaced by the block of the same name
+    /// given to a parent template.
+    Block(Access<'a>, Position, Vec<Part<'a>>),
+    /// `{{< "parent"}}{{$name}}...{{/name}}{{/"parent"}}`, rendering a parent
+    /// template with the blocks inside it.
+    Parent(&'a str, Position, Vec<Part<'a>>),
+    /// `{{#*inline "name"}}...{{/inline}}`, a partial that can be included
+    /// from the parts it is defined in.
+    Inline(&'a str, Position, Vec<Part<'a>>),
+    Comment,
+}
+
+#[derive(Debug, Eq, PartialEq)]
+pub struct Include<'a> {
+    pub name: IncludeName<'a>,
+    /// `{{> "name" path}}`, rendering the template with the value at `path`.
+    pub context: Option<Access<'a>>,
+    /// `{{> "name" arg=path}}`, resolving `arg` to the value at `path`
+    /// before the context.
+    pub args: Vec<(&'a str, Access<'a>)>,
+    pub position: Position,
+    /// Whitespace preceding a standalone include tag, added to every line of
+    /// the included template.
+    pub indent: &'a str,
+}
+
+#[derive(Debug, Eq, PartialEq)]
+pub enum IncludeName<'a> {
+    /// `{{> "path"}}`
+    Path(&'a str),
+    /// `{{> *path}}`, naming the template with a string in the data.
+    Dynamic(Access<'a>),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Access<'a> {
+    Variant(&'a str),
+    Path(Vec<Field<'a>>),
+    This,
+    /// `../path`, resolved against the n:th enclosing context.
+    Parent(usize, Vec<Field<'a>>),
+    /// `@root.path`, resolved against the data passed to the renderer.
+    Root(Vec<Field<'a>>),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Field<'a> {
+    Index(usize),
+    Nth(usize),
+    Named(&'a str),
+}
+
+impl fmt::Display for Access<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Access::Variant(name) => write!(f, "{}", name),
+            Access::This => write!(f, "."),
+            Access::Path(fields) => write_fields(f, fields, true),
+            Access::Parent(depth, fields) => {
+                write!(f, "{}", "../".repeat(*depth))?;
+                write_fields(f, fields, true)
+            }
+            Access::Root(fields) => {
+                write!(f, "@root")?;
+                write_fields(f, fields, false)
+            }
+        }
+    }
+}
+
+fn write_fields(f: &mut fmt::Formatter<'_>, fields: &[Field], leading: bool) -> fmt::Result {
+    for (i, field) in fields.iter().enumerate() {
+        let dot = if i == 0 && leading { "" } else { "." };
+        match field {
+            Field::Index(index) => write!(f, "[{}]", index)?,
+            Field::Nth(n) => write!(f, "{}{}", dot, n)?,
+            Field::Named(name) => write!(f, "{}{}", dot, name)?,
+        }
+    }
+    Ok(())
+}
+
+#[derive(Debug)]
+pub enum PathPart<'a> {
+    Index(usize),
+    Nth(usize),
+    Named(&'a str),
+    Dot,
+}
+
+/// Parser error pointing at `width` bytes of source starting from `at`.
+#[derive(Debug)]
+struct Error<'a> {
+    at: Span<'a>,
+    width: usize,
+    kind: Box<ParseErrorKind>,
+}
+
+impl<'a> Error<'a> {
+    fn new(at: Span<'a>, width: usize, kind: ParseErrorKind) -> Self {
+        Error {
+            at,
+            width,
+            kind: Box::new(kind),
+        }
+    }
+
+    fn into_parse_error(self, source: &str) -> ParseError {
+        let offset = self.at.location_offset();
+        let line_start = source[..offset].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = source[offset..]
+            .find('\n')
+            .map_or(source.len(), |i| offset + i);
+        let mut end = (offset + self.width).min(source.len());
+        while !source.is_char_boundary(end) {
+            end += 1;
+        }
+        let width = source[offset..end].chars().count();
+        ParseError {
+            kind: self.kind,
+            line: self.at.location_line(),
+            column: self.at.get_utf8_column(),
+            width,
+            snippet: source[line_start..line_end]
+                .trim_end_matches('\r')
+                .to_owned(),
+        }
+    }
+}
+
+impl<'a> nom::error::ParseError<Span<'a>> for Error<'a> {
+    fn from_error_kind(input: Span<'a>, _kind: ErrorKind) -> Self {
+        Error::new(input, first_char_len(input), ParseErrorKind::InvalidSyntax)
+    }
+
+    fn append(_input: Span<'a>, _kind: ErrorKind, other: Self) -> Self {
+        other
+    }
+}
+
+fn failure(at: Span, width: usize, kind: ParseErrorKind) -> Err<Error> {
+    Err::Failure(Error::new(at, width, kind))
+}
+
+/// Byte length of the first character of `span`, for errors pointing at it.
+fn first_char_len(span: Span) -> usize {
+    span.fragment().chars().next().map_or(0, char::len_utf8)
+}
+
+fn position(span: Span) -> Position {
+    Position {
+        line: span.location_line(),
+        column: span.get_utf8_column(),
+    }
+}
+
+/// Returns the source between `from` and `to`.
+fn consumed<'a>(from: Span<'a>, to: Span<'a>) -> &'a str {
+    &from.fragment()[..to.location_offset() - from.location_offset()]
+}
+
+/// Location and source of a parsed tag.
+struct Tag<'a> {
+    span: Span<'a>,
+    source: &'a str,
+}
+
+/// A tag opening a part that is closed by a tag with the same name.
+enum Opening<'a> {
+    Section(Access<'a>),
+    InvertedSection(Access<'a>),
+    Block(Access<'a>),
+    Parent(&'a str),
+    Inline(&'a str),
+}
+
+/// The name in a closing tag, `{{/name}}` or `{{/"path"}}`.
+enum Closing<'a> {
+    Access(Access<'a>),
+    Path(&'a str),
+}
+
+impl<'a> Opening<'a> {
+    fn is_closed_by(&self, closing: &Closing) -> bool {
+        match (self, closing) {
+            (
+                Opening::Section(access)
+                | Opening::InvertedSection(access)
+                | Opening::Block(access),
+                Closing::Access(name),
+            ) => access == name,
+            (Opening::Parent(path), Closing::Path(name)) => path == name,
+            (Opening::Inline(_), Closing::Access(Access::Path(fields))) => {
+                fields[..] == [Field::Named("inline")]
+            }
+            _ => false,
+        }
+    }
+
+    /// The name expected in the closing tag.
+    fn name(&self) -> String {
+        match self {
+            Opening::Section(access)
+            | Opening::InvertedSection(access)
+            | Opening::Block(access) => access.to_string(),
+            Opening::Parent(path) => format!("\"{}\"", path),
+            Opening::Inline(_) => "inline".into(),
+        }
+    }
+
+    fn into_part(self, position: Position, contents: Vec<Part<'a>>) -> Part<'a> {
+        match self {
+            Opening::Section(access) => Part::Section(access, position, contents),
+            Opening::InvertedSection(access) => Part::InvertedSection(access, position, contents),
+            Opening::Block(access) => Part::Block(access, position, contents),
+            // Only the blocks and inline partials of a parent tag are used.
+            Opening::Parent(path) => {
+                let blocks = contents
+                    .into_iter()
+                    .filter(|part| matches!(part, Part::Block(..) | Part::Inline(..)))
+                    .collect();
+                Part::Parent(path, position, blocks)
+            }
+            Opening::Inline(name) => Part::Inline(name, position, contents),
+        }
+    }
+}
+
+/// Options controlling how template sources are parsed.
+#[derive(Clone, Debug)]
+pub struct ParseOptions {
+    legacy_comments: bool,
+    delimiters: (String, String),
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        ParseOptions {
+            legacy_comments: false,
+            delimiters: ("{{".into(), "}}".into()),
+        }
+    }
+}
+
+impl ParseOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Also accept the `\{{ comment }}` syntax of earlier versions.
+    pub fn legacy_comments(mut self, legacy_comments: bool) -> Self {
+        self.legacy_comments = legacy_comments;
+        self
+    }
+
+    /// Use `open` and `close` instead of `{{` and `}}` as the initial tag
+    /// delimiters. Templates can still switch them with a `{{=<% %>=}}` tag.
+    pub fn delimiters(mut self, open: &str, close: &str) -> Self {
+        self.delimiters = (open.into(), close.into());
+        self
+    }
+}
+
+pub fn parse<'a>(
+    s: &'a str,
+    options: &ParseOptions,
+) -> std::result::Result<Vec<Part<'a>>, ParseError> {
+    let span = Span::new(s);
+    let (open, close) = &options.delimiters;
+    if !is_delimiter(open) || !is_delimiter(close) {
+        return Err(Error::new(span, 0, ParseErrorKind::InvalidDelimiters).into_parse_error(s));
+    }
+
+    let parser = Parser {
+        source: s,
+        legacy_comments: options.legacy_comments,
+        open,
+        close,
+        parts: Vec::new(),
+        sections: Vec::new(),
+    };
+    parser.parse(span).map_err(|err| err.into_parse_error(s))
+}
+
+fn is_inline_space(c: char) -> bool {
+    c == ' ' || c == '\t'
+}
+
+fn is_delimiter(s: &str) -> bool {
+    !s.is_empty() && !s.contains(|c: char| c.is_whitespace() || c == '=')
+}
+
+/// Splits a template into text and tags, building the section tree as it
+/// goes. Delimiters start out as configured in [`ParseOptions`] and change
+/// whenever a `{{=<% %>=}}` tag is parsed.
+struct Parser<'a, 'o> {
+    source: &'a str,
+    legacy_comments: bool,
+    open: &'o str,
+    close: &'o str,
+    parts: Vec<Part<'a>>,
+    sections: Vec<OpenSection<'a>>,
+}
+
+/// A tag parsed by [`Parser::tag`].
+enum Token<'a> {
+    Part(Part<'a>),
+    Open(Tag<'a>, Opening<'a>),
+    Close(Tag<'a>, Closing<'a>),
+    Delimiters(&'a str, &'a str),
+}
+
+impl Token<'_> {
+    /// Whether the tag is removed along with its line when nothing but
+    /// whitespace shares the line with it.
+    fn can_stand_alone(&self) -> bool {
+        !matches!(
+            self,
+            Token::Part(Part::Variable(..) | Part::UnescapedVariable(..))
+        )
+    }
+}
+
+/// Whether whitespace before and after a tag is trimmed by `{{~` and `~}}`
+/// markers.
+struct Trim {
+    before: bool,
+    after: bool,
+}
+
+/// A section whose closing tag has not been parsed yet.
+struct OpenSection<'a> {
+    tag: Tag<'a>,
+    opening: Opening<'a>,
+    /// Parts preceding the section in its enclosing section.
+    outer: Vec<Part<'a>>,
+}
+
+impl<'a: 'o, 'o> Parser<'a, 'o> {
+    fn parse(mut self, mut input: Span<'a>) -> std::result::Result<Vec<Part<'a>>, Error<'a>> {
+        loop {
+            let (rest, text) = self.text(input);
+            if !text.is_empty() {
+                self.parts.push(Part::Text(text));
+            }
+            if rest.is_empty() {
+                break;
+            }
+            let (mut end, (mut token, trim)) = match self.tag(rest) {
+                Ok(ok) => ok,
+                Err(Err::Error(err) | Err::Failure(err)) => return Err(err),
+                Err(Err::Incomplete(_)) => {
+                    return Err(Error::new(rest, rest.len(), self.unclosed_tag()))
+                }
+            };
+            if trim.before {
+                self.trim_text_end(str::trim_end);
+            }
+            if trim.after {
+                end = end.take_split(end.len() - end.trim_start().len()).0;
+            }
+            if !trim.before && !trim.after && token.can_stand_alone() {
+                if let Some((indent, after)) = self.standalone(rest, end) {
+                    self.trim_text_end(|text| text.strip_suffix(indent).unwrap_or(text));
+                    if let Token::Part(Part::Include(include)) = &mut token {
+                        include.indent = indent;
+                    }
+                    end = after;
+                }
+            }
+            self.apply(token)?;
+            input = end;
+        }
+
+        match self.sections.pop() {
+            Some(section) => Err(Error::new(
+                section.tag.span,
+                section.tag.source.len(),
+                ParseErrorKind::UnclosedSection {
+                    open: section.tag.source.into(),
+                    expected: self.closing_tag(&section.opening),
+                },
+            )),
+            None => Ok(self.parts),
+        }
+    }
+
+    /// Splits off the text preceding the next tag.
+    fn text(&self, input: Span<'a>) -> (Span<'a>, &'a str) {
+        let fragment = input.fragment();
+        let end = match fragment.find(self.open) {
+            Some(end) if self.legacy_comments && fragment[..end].ends_with('\\') => end - 1,
+            Some(end) => end,
+            None => fragment.len(),
+        };
+        let (rest, text) = input.take_split(end);
+        (rest, text.fragment())
+    }
+
+    /// Parses a tag along with its `~` whitespace control markers.
+    fn tag(&self, input: Span<'a>) -> Result<'a, (Token<'a>, Trim)> {
+        let legacy = self.legacy_comments && input.fragment().starts_with('\\');
+        let (rest, _) = pair(cond(legacy, tag("\\")), tag(self.open))(input)?;
+        let (rest, before) = opt(tag("~"))(rest)?;
+
+        let (end, token) = if legacy {
+            let (rest, _) = self.comment_body(input, rest)?;
+            (rest, Token::Part(Part::Comment))
+        } else {
+            self.token(input, rest)?
+        };
+        let source = consumed(input, end);
+        let trim = Trim {
+            before: before.is_some(),
+            after: source[..source.len() - self.close.len()].ends_with('~'),
+        };
+        Ok((end, (token, trim)))
+    }
+
+    /// Parses the rest of a tag that started at `input`.
+    fn token(&self, input: Span<'a>, rest: Span<'a>) -> Result<'a, Token<'a>> {
+        let (rest, sigil) = opt(one_of("!=#^$</>{&"))(rest)?;
+        match sigil {
+            Some('!') => {
+                let (rest, _) = self.comment_body(input, rest)?;
+                Ok((rest, Token::Part(Part::Comment)))
+            }
+            Some('=') => self.set_delimiters(input, rest),
+            Some('#') if rest.starts_with("*inline") => {
+                let (rest, name) =
+                    preceded(tag("*inline"), delimited(space0, file_path, space0))(rest)?;
+                let (rest, tag) = self.tag_end(input, rest)?;
+                Ok((rest, Token::Open(tag, Opening::Inline(&name))))
+            }
+            Some(sigil @ ('#' | '^' | '$')) => {
+                let (rest, access) = tag_access(rest)?;
+                let (rest, tag) = self.tag_end(input, rest)?;
+                let opening = match sigil {
+                    '#' => Opening::Section(access),
+                    '^' => Opening::InvertedSection(access),
+                    _ => Opening::Block(access),
+                };
+                Ok((rest, Token::Open(tag, opening)))
+            }
+            Some('<') => {
+                let (rest, path) = delimited(space0, file_path, space0)(rest)?;
+                let (rest, tag) = self.tag_end(input, rest)?;
+                Ok((rest, Token::Open(tag, Opening::Parent(&path))))
+            }
+            Some('/') => {
+                let quoted: Result<_> = preceded(space0, tag("\""))(rest);
+                let (rest, closing) = if quoted.is_ok() {
+                    let (rest, path) = delimited(space0, file_path, space0)(rest)?;
+                    (rest, Closing::Path(&path))
+                } else {
+                    let (rest, access) = tag_access(rest)?;
+                    (rest, Closing::Access(access))
+                };
+                let (rest, tag) = self.tag_end(input, rest)?;
+                Ok((rest, Token::Close(tag, closing)))
+            }
+            Some('>') => {
+                let (rest, _) = space0(rest)?;
+                let (rest, name) = match opt(tag("*"))(rest)? {
+                    (rest, Some(_)) => {
+                        let (rest, access) = tag_access(rest)?;
+                        (rest, IncludeName::Dynamic(access))
+                    }
+                    (rest, None) => {
+                        let (rest, path) = terminated(file_path, space0)(rest)?;
+                        (rest, IncludeName::Path(&path))
+                    }
+                };
+                let (rest, context) = opt(terminated(access, pair(not(tag("=")), space0)))(rest)?;
+                let (rest, args) = many0(terminated(
+                    separated_pair(identifier, tag("="), access),
+                    space0,
+                ))(rest)?;
+                let (rest, _) = tag_close(input, rest, self.close)?;
+                let include = Include {
+                    name,
+                    context,
+                    args: args
+                        .into_iter()
+                        .map(|(name, access)| (*name.fragment(), access))
+                        .collect(),
+                    position: position(input),
+                    indent: "",
+                };
+                Ok((rest, Token::Part(Part::Include(include))))
+            }
+            Some(sigil) => {
+                let (rest, access) = tag_access(rest)?;
+                let (rest, _) = if sigil == '{' {
+                    preceded(tag("}"), |i| tag_close(input, i, self.close))(rest).map_err(
+                        |_: Err<Error>| {
+                            failure(
+                                input,
+                                consumed(input, rest).len(),
+                                ParseErrorKind::UnclosedTag {
+                                    expected: format!("}}{}", self.close),
+                                },
+                            )
+                        },
+                    )?
+                } else {
+                    tag_close(input, rest, self.close)?
+                };
+                let part = Part::UnescapedVariable(access, position(input));
+                Ok((rest, Token::Part(part)))
+            }
+            None => {
+                let (rest, access) = tag_access(rest)?;
+                let (rest, _) = tag_close(input, rest, self.close)?;
+                let part = Part::Variable(access, position(input));
+                Ok((rest, Token::Part(part)))
+            }
+        }
+    }
+
+    /// Adds a parsed tag to the section tree.
+    fn apply(&mut self, token: Token<'a>) -> std::result::Result<(), Error<'a>> {
+        match token {
+            Token::Part(part) => self.parts.push(part),
+            Token::Open(tag, opening) => self.sections.push(OpenSection {
+                tag,
+                opening,
+                outer: mem::take(&mut self.parts),
+            }),
+            Token::Close(tag, closing) => self.close_section(tag, closing)?,
+            Token::Delimiters(open, close) => {
+                self.open = open;
+                self.close = close;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the whitespace preceding a tag spanning `start..end` and the
+    /// input following its line, if nothing else shares the line with the
+    /// tag.
+    fn standalone(&self, start: Span<'a>, end: Span<'a>) -> Option<(&'a str, Span<'a>)> {
+        let before = &self.source[..start.location_offset()];
+        let indent = &before[before.rfind('\n').map_or(0, |i| i + 1)..];
+        if !indent.chars().all(is_inline_space) {
+            return None;
+        }
+        // The indentation may have been trimmed away by a preceding `~`.
+        let text = match self.parts.last() {
+            Some(Part::Text(text)) => text,
+            _ => "",
+        };
+        if !text.ends_with(indent) {
+            return None;
+        }
+
+        let after = end.fragment();
+        let trailing = after.len() - after.trim_start_matches(is_inline_space).len();
+        let line_end = match &after[trailing..] {
+            "" => 0,
+            rest if rest.starts_with('\n') => 1,
+            rest if rest.starts_with("\r\n") => 2,
+            _ => return None,
+        };
+        Some((indent, end.take_split(trailing + line_end).0))
+    }
+
+    /// Trims the end of the text preceding a tag with `trim`.
+    fn trim_text_end(&mut self, trim: impl FnOnce(&'a str) -> &'a str) {
+        if let Some(Part::Text(text)) = self.parts.last_mut() {
+            *text = trim(text);
+            if text.is_empty() {
+                self.parts.pop();
+            }
+        }
+    }
+
+    /// Parses the closing delimiter of a tag that started at `open`.
+    fn tag_end(&self, open: Span<'a>, input: Span<'a>) -> Result<'a, Tag<'a>> {
+        let (rest, _) = tag_close(open, input, self.close)?;
+        Ok((
+            rest,
+            Tag {
+                span: open,
+                source: consumed(open, rest),
+            },
+        ))
+    }
+
+    fn close_section(
+        &mut self,
+        end: Tag<'a>,
+        closing: Closing<'a>,
+    ) -> std::result::Result<(), Error<'a>> {
+        let section = self.sections.pop().ok_or_else(|| {
+            Error::new(
+                end.span,
+                end.source.len(),
+                ParseErrorKind::StrayClosingTag {
+                    close: end.source.into(),
+                },
+            )
+        })?;
+        if !section.opening.is_closed_by(&closing) {
+            return Err(Error::new(
+                end.span,
+                end.source.len(),
+                ParseErrorKind::MismatchedSection {
+                    open: section.tag.source.into(),
+                    open_line: section.tag.span.location_line(),
+                    open_column: section.tag.span.get_utf8_column(),
+                    close: end.source.into(),
+                    expected: self.closing_tag(&section.opening),
+                },
+            ));
+        }
+
+        let contents = mem::replace(&mut self.parts, section.outer);
+        let part = section
+            .opening
+            .into_part(position(section.tag.span), contents);
+        self.parts.push(part);
+        Ok(())
+    }
+
+    /// Parses everything up to and including the delimiter closing a comment.
+    fn comment_body(&self, open: Span<'a>, input: Span<'a>) -> Result<'a, ()> {
+        let (rest, _) = take_until(self.close)(input).map_err(|_: Err<Error>| {
+            failure(open, consumed(open, input).len(), self.unclosed_tag())
+        })?;
+        tag_close(open, rest, self.close)
+    }
+
+    /// Parses the rest of a `{{=<% %>=}}` tag and switches to its delimiters.
+    fn set_delimiters(&self, open: Span<'a>, input: Span<'a>) -> Result<'a, Token<'a>> {
+        let delimiter = || take_till1(|c: char| c.is_whitespace() || c == '=');
+        let (rest, (_, new_open, _, new_close, _, _)) =
+            tuple((space0, delimiter(), space1, delimiter(), space0, tag("=")))(input).map_err(
+                |_: Err<Error>| {
+                    let width = input
+                        .fragment()
+                        .find(self.close)
+                        .map_or(0, |i| i + self.close.len());
+                    failure(
+                        open,
+                        consumed(open, input).len() + width,
+                        ParseErrorKind::InvalidDelimiters,
+                    )
+                },
+            )?;
+        let (rest, _) = tag_close(open, rest, self.close)?;
+        Ok((
+            rest,
+            Token::Delimiters(new_open.fragment(), new_close.fragment()),
+        ))
+    }
+
+    fn unclosed_tag(&self) -> ParseErrorKind {
+        ParseErrorKind::UnclosedTag {
+            expected: self.close.into(),
+        }
+    }
+
+    fn closing_tag(&self, opening: &Opening) -> String {
+        format!("{}/{}{}", self.open, opening.name(), self.close)
+    }
+}
+
+fn file_path(input: Span) -> Result<Span> {
+    let (rest, _) = tag("\"")(input).map_err(|_: Err<Error>| {
+        failure(
+            input,
+            first_char_len(input),
+            ParseErrorKind::ExpectedIncludePath,
+        )
+    })?;
+    let (rest, path) = recognize(many0_count(alt((
+        is_not("\"\\\r\n}"),
+        preceded(tag("\\"), tag("\"")),
+    ))))(rest)?;
+    if rest.starts_with('\\') {
+        let escaped = rest.take_split(1).0;
+        return Err(failure(
+            rest,
+            1 + first_char_len(escaped),
+            ParseErrorKind::InvalidEscape,
+        ));
+    }
+    let (rest, _) = tag("\"")(rest).map_err(|_: Err<Error>| {
+        failure(
+            input,
+            consumed(input, rest).len(),
+            ParseErrorKind::UnterminatedIncludePath,
+        )
+    })?;
+    if path.is_empty() {
+        return Err(failure(input, 2, ParseErrorKind::ExpectedIncludePath));
+    }
+    Ok((rest, path))
+}
+
+fn tag_access(input: Span) -> Result<Access> {
+    let (input, _) = space0(input)?;
+    let (input, access) = access(input)
+        .map_err(|_| failure(input, first_char_len(input), ParseErrorKind::InvalidAccess))?;
+    let (input, _) = space0(input)?;
+    Ok((input, access))
+}
+
+/// Parses the `close` delimiter of a tag that started at `open`, along with
+/// an optional `~` marker before it.
+fn tag_close<'a>(open: Span<'a>, input: Span<'a>, close: &str) -> Result<'a, ()> {
+    let (input, _) = opt(tag("~"))(input)?;
+    let (input, _) = tag(close)(input).map_err(|_: Err<Error>| {
+        failure(
+            open,
+            consumed(open, input).len(),
+            ParseErrorKind::UnclosedTag {
+                expected: close.into(),
+            },
+        )
+    })?;
+    Ok((input, ()))
+}
+
+fn access(input: Span) -> Result<Access> {
+    alt((
+        access_root,
+        access_parent,
+        access_this,
+        access_variant,
+        access_path,
+    ))(input)
+}
+
+fn access_root(input: Span) -> Result<Access> {
+    let (input, _) = tag("@root")(input)?;
+    let (input, fields) = opt(preceded(tag("."), path_fields))(input)?;
+    Ok((input, Access::Root(fields.unwrap_or_default())))
+}
+
+fn access_parent(input: Span) -> Result<Access> {
+    let (input, depth) = many1_count(tag("../"))(input)?;
+    let (input, fields) = opt(path_fields)(input)?;
+    Ok((input, Access::Parent(depth, fields.unwrap_or_default())))
+}
+
+fn access_this(input: Span) -> Result<Access> {
+    let (input, _) = tag(".")(input)?;
+    Ok((input, Access::This))
+}
+
+fn access_variant(input: Span) -> Result<Access> {
+    let (input, name) = recognize(pair(upper1, alphanumeric1))(input)?;
+    Ok((input, Access::Variant(&name)))
+}
+
+fn access_path(input: Span) -> Result<Access> {
+    let (input, fields) = path_fields(input)?;
+    Ok((input, Access::Path(fields)))
+}
+
+fn path_fields(input: Span) -> Result<Vec<Field>> {
+    fold_many1(path_part, Vec::new, |mut acc, part| {
+        match part {
+            PathPart::Index(i) => acc.push(Field::Index(i)),
+            PathPart::Nth(i) => acc.push(Field::Nth(i)),
+            PathPart::Named(n) => acc.push(Field::Named(n)),
+            PathPart::Dot => {}
+        };
+        acc
+    })(input)
+}
+
+fn path_part(input: Span) -> Result<PathPart> {
+    alt((field_dot, field_index, field_nth, field_identifier))(input)
+}
+
+fn field_dot(input: Span) -> Result<PathPart> {
+    let (input, _) = tag(".")(input)?;
+    Ok((input, PathPart::Dot))
+}
+
+fn field_index(input: Span) -> Result<PathPart> {
+    let (input, number) = delimited(tag("["), nom::character::complete::u128, tag("]"))(input)?;
+    Ok((input, PathPart::Index(number as usize)))
+}
+
+fn field_nth(input: Span) -> Result<PathPart> {
+    let (input, number) = nom::character::complete::u128(input)?;
+    Ok((input, PathPart::Nth(number as usize)))
+}
+
+fn field_identifier(input: Span) -> Result<PathPart> {
+    let (input, name) = identifier(input)?;
+    Ok((input, PathPart::Named(&name)))
+}
+
+fn identifier(input: Span) -> Result<Span> {
+    recognize(pair(
+        alt((alpha1, tag("_"))),
+        many0_count(alt((alphanumeric1, tag("_")))),
+    ))(input)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn variable() {
+        use Field::*;
+
+        let this_var = parse("{{ . }}", &ParseOptions::default()).unwrap();
+        assert_eq!(
+            this_var,
+            vec![Part::Variable(
+                Access::This,
+                Position { line: 1, column: 1 }
+            )]
+        );
+
+        let path_var = parse("\n  {{ foo[12].1 }}", &ParseOptions::default()).unwrap();
+        assert_eq!(
+            path_var[1],
+            Part::Variable(
+                Access::Path(vec![Named("foo"), Index(12), Nth(1)]),
+                Position { line: 2, column: 3 }
+            )
+        );
+    }
+
+    #[test]
+    fn unescaped_variable() {
+        let parts = parse("{{{ a }}}{{&b}}", &ParseOptions::default()).unwrap();
+        assert_eq!(
+            parts,
+            vec![
+                Part::UnescapedVariable(
+                    Access::Path(vec![Field::Named("a")]),
+                    Position { line: 1, column: 1 }
+                ),
+                Part::UnescapedVariable(
+                    Access::Path(vec![Field::Named("b")]),
+                    Position {
+                        line: 1,
+                        column: 10
+                    }
+                ),
+            ]
+        );
+
+        let err = parse("{{{ a }}", &ParseOptions::default()).unwrap_err();
+        assert_eq!(
+            *err.kind,
+            ParseErrorKind::UnclosedTag {
+                expected: "}}}".into()
+            }
+        );
+    }
+
+    #[test]
+    fn comments() {
+        let options = ParseOptions::default();
+        let parts = parse("a{{! {b}\n c } }}d{{!}}", &options).unwrap();
+        assert_eq!(
+            parts,
+            vec![
+                Part::Text("a"),
+                Part::Comment,
+                Part::Text("d"),
+                Part::Comment
+            ]
+        );
+
+        let err = parse("a{{! b }", &options).unwrap_err();
+        assert_eq!((err.line, err.column), (1, 2));
+    }
+
+    #[test]
+    fn legacy_comments() {
+        let parts = parse("a\\{{ b } }}c", &ParseOptions::new().legacy_comments(true)).unwrap();
+        assert_eq!(parts, vec![Part::Text("a"), Part::Comment, Part::Text("c")]);
+
+        let parts = parse("a\\{{ b }}", &ParseOptions::default()).unwrap();
+        assert_eq!(parts[0], Part::Text("a\\"));
+
+        let parts = parse("C:\\", &ParseOptions::new().legacy_comments(true)).unwrap();
+        assert_eq!(parts, vec![Part::Text("C:\\")]);
+    }
+
+    #[test]
+    fn mismatched_section() {
+        let err = parse(
+            "<ul>\n  {{#items}}{{.}}{{/item}}\n</ul>",
+            &ParseOptions::default(),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ParseError {
+                kind: Box::new(ParseErrorKind::MismatchedSection {
+                    open: "{{#items}}".into(),
+                    open_line: 2,
+                    open_column: 3,
+                    close: "{{/item}}".into(),
+                    expected: "{{/items}}".into(),
+                }),
+                line: 2,
+                column: 18,
+                width: 9,
+                snippet: "  {{#items}}{{.}}{{/item}}".into(),
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            r#"error: section `{{#items}}` opened at 2:3 was closed by `{{/item}}` at 2:18
+  |
+2 |   {{#items}}{{.}}{{/item}}
+  |                  ^^^^^^^^^ expected `{{/items}}`"#
+        );
+    }
+
+    #[test]
+    fn unclosed_section() {
+        let err = parse("a\n{{^ items }}\nb", &ParseOptions::default()).unwrap_err();
+        assert_eq!(
+            *err.kind,
+            ParseErrorKind::UnclosedSection {
+                open: "{{^ items }}".into(),
+                expected: "{{/items}}".into(),
+            }
+        );
+        assert_eq!((err.line, err.column, err.width), (2, 1, 12));
+    }
+
+    #[test]
+    fn stray_closing_tag() {
+        let err = parse("a {{/items}}", &ParseOptions::default()).unwrap_err();
+        assert_eq!(
+            *err.kind,
+            ParseErrorKind::StrayClosingTag {
+                close: "{{/items}}".into()
+            }
+        );
+        assert_eq!((err.line, err.column), (1, 3));
+    }
+
+    #[test]
+    fn unclosed_tag() {
+        let err = parse("Hello {{name", &ParseOptions::default()).unwrap_err();
+        assert_eq!(
+            *err.kind,
+            ParseErrorKind::UnclosedTag {
+                expected: "}}".into()
+            }
+        );
+        assert_eq!((err.line, err.column, err.width), (1, 7, 6));
+        assert_eq!(err.snippet, "Hello {{name");
+    }
+
+    #[test]
+    fn invalid_access() {
+        let err = parse("{{#items}}{{ -1 }}{{/items}}", &ParseOptions::default()).unwrap_err();
+        assert_eq!(*err.kind, ParseErrorKind::InvalidAccess);
+        assert_eq!(err.column, 14);
+    }
+
+    #[test]
+    fn non_ascii_errors() {
+        for (source, kind, column) in [
+            ("{{ → }}", ParseErrorKind::InvalidAccess, 4),
+            ("{{/→}}", ParseErrorKind::InvalidAccess, 4),
+            ("{{> *→ }}", ParseErrorKind::InvalidAccess, 6),
+            ("{{> «header» }}", ParseErrorKind::ExpectedIncludePath, 5),
+        ] {
+            let err = parse(source, &ParseOptions::default()).unwrap_err();
+            assert_eq!(*err.kind, kind, "{}", source);
+            assert_eq!((err.column, err.width), (column, 1), "{}", source);
+        }
+    }
+
+    #[test]
+    fn include_paths() {
+        let err = parse("{{> header.html }}", &ParseOptions::default()).unwrap_err();
+        assert_eq!(*err.kind, ParseErrorKind::ExpectedIncludePath);
+        assert_eq!(err.column, 5);
+
+        let err = parse("{{> \"header.html}}", &ParseOptions::default()).unwrap_err();
+        assert_eq!(*err.kind, ParseErrorKind::UnterminatedIncludePath);
+        assert_eq!((err.column, err.width), (5, 12));
+
+        for source in [
+            "{{> \"\"}}",
+            "{{< \"\"}}{{/\"\"}}",
+            "{{#*inline \"\"}}{{/inline}}",
+        ] {
+            let err = parse(source, &ParseOptions::default()).unwrap_err();
+            assert_eq!(*err.kind, ParseErrorKind::ExpectedIncludePath, "{}", source);
+            assert_eq!(err.width, 2, "{}", source);
+        }
+
+        let err = parse("{{> \"}}", &ParseOptions::default()).unwrap_err();
+        assert_eq!(*err.kind, ParseErrorKind::UnterminatedIncludePath);
+        assert_eq!((err.column, err.width), (5, 1));
+
+        let err = parse("{{> \"partials\\header.html\"}}", &ParseOptions::default()).unwrap_err();
+        assert_eq!(*err.kind, ParseErrorKind::InvalidEscape);
+        assert_eq!((err.column, err.width), (14, 2));
+
+        let parts = parse("{{> *widget.template }}", &ParseOptions::default()).unwrap();
+        assert_eq!(
+            parts,
+            vec![Part::Include(Include {
+                name: IncludeName::Dynamic(Access::Path(vec![
+                    Field::Named("widget"),
+                    Field::Named("template")
+                ])),
+                context: None,
+                args: vec![],
+                position: Position { line: 1, column: 1 },
+                indent: "",
+            })]
+        );
+
+        let parts = parse(
+            "{{> \"card.html\" page title=page.title body=intro }}",
+            &ParseOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            parts,
+            vec![Part::Include(Include {
+                name: IncludeName::Path("card.html"),
+                context: Some(Access::Path(vec![Field::Named("page")])),
+                args: vec![
+                    (
+                        "title",
+                        Access::Path(vec![Field::Named("page"), Field::Named("title")])
+                    ),
+                    ("body", Access::Path(vec![Field::Named("intro")])),
+                ],
+                position: Position { line: 1, column: 1 },
+                indent: "",
+            })]
+        );
+    }
+
+    #[test]
+    fn set_delimiters() {
+        let parts = parse(
+            "{{=<% %>=}}<% a %>{{b}}<%={{ }}=%>{{c}}",
+            &ParseOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            parts,
+            vec![
+                Part::Variable(
+                    Access::Path(vec![Field::Named("a")]),
+                    Position {
+                        line: 1,
+                        column: 12
+                    }
+                ),
+                Part::Text("{{b}}"),
+                Part::Variable(
+                    Access::Path(vec![Field::Named("c")]),
+                    Position {
+                        line: 1,
+                        column: 35
+                    }
+                ),
+            ]
+        );
+
+        let err = parse("a {{= <% =}}", &ParseOptions::default()).unwrap_err();
+        assert_eq!(*err.kind, ParseErrorKind::InvalidDelimiters);
+        assert_eq!((err.column, err.width), (3, 10));
+    }
+
+    #[test]
+    fn default_delimiters() {
+        let options = ParseOptions::new().delimiters("[[", "]]");
+        let parts = parse("[[#a]]{{b}}[[{c}]][[/a]]", &options).unwrap();
+        assert_eq!(
+            parts,
+            vec![Part::Section(
+                Access::Path(vec![Field::Named("a")]),
+                Position { line: 1, column: 1 },
+                vec![
+                    Part::Text("{{b}}"),
+                    Part::UnescapedVariable(
+                        Access::Path(vec![Field::Named("c")]),
+                        Position {
+                            line: 1,
+                            column: 12
+                        }
+                    ),
+                ]
+            )]
+        );
+
+        let err = parse("[[#a]]", &options).unwrap_err();
+        assert!(err.to_string().ends_with("expected `[[/a]]`"));
+
+        let err = parse("a", &ParseOptions::new().delimiters("{ {", "}}")).unwrap_err();
+        assert_eq!(*err.kind, ParseErrorKind::InvalidDelimiters);
+    }
+
+    #[test]
+    fn standalone_lines() {
+        let options = ParseOptions::default();
+        let parts = parse(
+            "a\n  {{#b}}  \r\n{{! c }}\n  d {{/b}}\n{{=<% %>=}}\n<%e%>\n",
+            &options,
+        )
+        .unwrap();
+        assert_eq!(
+            parts,
+            vec![
+                Part::Text("a\n"),
+                Part::Section(
+                    Access::Path(vec![Field::Named("b")]),
+                    Position { line: 2, column: 3 },
+                    vec![Part::Comment, Part::Text("  d ")]
+                ),
+                Part::Text("\n"),
+                Part::Variable(
+                    Access::Path(vec![Field::Named("e")]),
+                    Position { line: 6, column: 1 }
+                ),
+                Part::Text("\n"),
+            ]
+        );
+
+        let parts = parse("{{a}} {{! b }}\n  {{> \"c\"}}", &options).unwrap();
+        assert_eq!(parts[1], Part::Text(" "));
+        assert_eq!(parts[3], Part::Text("\n"));
+        assert_eq!(parts.len(), 5);
+    }
+
+    #[test]
+    fn whitespace_control() {
+        let source = "a, \n {{~#items~}}\n {{~{name}~}} ,\n{{~/items}}  b{{! c ~}}\n d";
+        let parts = parse(source, &ParseOptions::default()).unwrap();
+        assert_eq!(
+            parts,
+            vec![
+                Part::Text("a,"),
+                Part::Section(
+                    Access::Path(vec![Field::Named("items")]),
+                    Position { line: 2, column: 2 },
+                    vec![
+                        Part::UnescapedVariable(
+                            Access::Path(vec![Field::Named("name")]),
+                            Position { line: 3, column: 2 }
+                        ),
+                        Part::Text(","),
+                    ]
+                ),
+                Part::Text("  b"),
+                Part::Comment,
+                Part::Text("d"),
+            ]
+        );
+
+        // The trimmed indentation no longer makes the next tag standalone.
+        let parts = parse("ab{{=<% %>=~}}\n   <%! c %>\n", &ParseOptions::default()).unwrap();
+        assert_eq!(
+            parts,
+            vec![Part::Text("ab"), Part::Comment, Part::Text("\n")]
+        );
+    }
+
+    #[test]
+    fn inheritance() {
+        let source = "{{< \"layout\"}}\nignored\n{{$title}}T{{/title}}\n{{/\"layout\"}}";
+        let parts = parse(source, &ParseOptions::default()).unwrap();
+        assert_eq!(
+            parts,
+            vec![Part::Parent(
+                "layout",
+                Position { line: 1, column: 1 },
+                vec![Part::Block(
+                    Access::Path(vec![Field::Named("title")]),
+                    Position { line: 3, column: 1 },
+                    vec![Part::Text("T")]
+                )]
+            )]
+        );
+
+        let err = parse("{{< \"a\"}}{{/a}}", &ParseOptions::default()).unwrap_err();
+        assert!(err.to_string().ends_with("expected `{{/\"a\"}}`"));
+    }
+
+    #[test]
+    fn inline_partials() {
+        let source = "{{#*inline \"row\"}}\n<tr>{{name}}</tr>\n{{/inline}}\n{{> \"row\"}}";
+        let parts = parse(source, &ParseOptions::default()).unwrap();
+        assert_eq!(
+            parts,
+            vec![
+                Part::Inline(
+                    "row",
+                    Position { line: 1, column: 1 },
+                    vec![
+                        Part::Text("<tr>"),
+                        Part::Variable(
+                            Access::Path(vec![Field::Named("name")]),
+                            Position { line: 2, column: 5 }
+                        ),
+                        Part::Text("</tr>\n"),
+                    ]
+                ),
+                Part::Include(Include {
+                    name: IncludeName::Path("row"),
+                    context: None,
+                    args: vec![],
+                    position: Position { line: 4, column: 1 },
+                    indent: "",
+                }),
+            ]
+        );
+
+        let err = parse("{{#*inline \"row\"}}{{/row}}", &ParseOptions::default()).unwrap_err();
+        assert!(err.to_string().ends_with("expected `{{/inline}}`"));
+    }
+
+    #[test]
+    fn access_path() {
+        use Field::*;
+
+        let (_, path) = access(LocatedSpan::new("foo.0.bar.1[12]")).unwrap();
+        assert_eq!(
+            path,
+            Access::Path(vec![Named("foo"), Nth(0), Named("bar"), Nth(1), Index(12)])
+        );
+        assert_eq!(path.to_string(), "foo.0.bar.1[12]");
+    }
+
+    #[test]
+    fn access_variant() {
+        let (_, variant) = access(LocatedSpan::new("FooBar")).unwrap();
+        assert_eq!(variant, Access::Variant("FooBar"));
+    }
+
+    #[test]
+    fn access_parent() {
+        use Field::*;
+
+        let (_, parent) = access(LocatedSpan::new("../../head.title")).unwrap();
+        assert_eq!(
+            parent,
+            Access::Parent(2, vec![Named("head"), Named("title")])
+        );
+        assert_eq!(parent.to_string(), "../../head.title");
+
+        let (_, parent) = access(LocatedSpan::new("../")).unwrap();
+        assert_eq!(parent, Access::Parent(1, vec![]));
+    }
+
+    #[test]
+    fn access_root() {
+        use Field::*;
+
+        let (_, root) = access(LocatedSpan::new("@root.head.title")).unwrap();
+        assert_eq!(root, Access::Root(vec![Named("head"), Named("title")]));
+        assert_eq!(root.to_string(), "@root.head.title");
+
+        let (_, root) = access(LocatedSpan::new("@root")).unwrap();
+        assert_eq!(root, Access::Root(vec![]));
+    }
+
+    #[test]
+    fn access_this() {
+        let (_, variant) = access(LocatedSpan::new(".")).unwrap();
+        assert_eq!(variant, Access::This);
+    }
+}
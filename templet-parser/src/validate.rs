@@ -0,0 +1,174 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use crate::{
+    errors::{Position, ValidationError},
+    names::include_key,
+    parse::{IncludeName, Part},
+};
+
+/// Checks that every include and parent tag of `templates` names an
+/// existing template or alias, and that no template always ends up
+/// including itself.
+///
+/// Dynamic includes cannot be checked. Includes of inline partials defined
+/// in another template are assumed to be rendered from within it.
+pub fn validate<'a>(
+    templates: &BTreeMap<&'a str, &'a [Part<'a>]>,
+    aliases: &HashMap<String, String>,
+) -> Result<(), Vec<ValidationError>> {
+    let get = |name: &str| {
+        let name = match templates.contains_key(name) {
+            true => name,
+            false => aliases.get(name)?,
+        };
+        templates.get_key_value(name).map(|(name, _)| *name)
+    };
+    let mut inlines = HashSet::new();
+    for &parts in templates.values() {
+        collect_inlines(parts, &mut inlines);
+    }
+
+    let mut errors = Vec::new();
+    let mut graph = BTreeMap::new();
+    for (&name, &parts) in templates {
+        let mut local = HashSet::new();
+        collect_inlines(parts, &mut local);
+        let mut includes = Vec::new();
+        collect_includes(parts, true, &mut includes);
+
+        let mut edges = Vec::new();
+        for include in includes {
+            if local.contains(include.name) {
+                continue;
+            }
+            let key = match include_key(name, include.name) {
+                Some(key) => key,
+                None => {
+                    errors.push(ValidationError::IncludeOutsideRoot {
+                        template: name.to_owned(),
+                        name: include.name.to_owned(),
+                        position: include.position,
+                    });
+                    continue;
+                }
+            };
+            match get(&key) {
+                Some(target) if include.unconditional => {
+                    edges.push((target, include.position));
+                }
+                Some(_) => {}
+                None if inlines.contains(include.name) => {}
+                None => errors.push(ValidationError::MissingInclude {
+                    template: name.to_owned(),
+                    name: key.into_owned(),
+                    position: include.position,
+                }),
+            }
+        }
+        graph.insert(name, edges);
+    }
+
+    let mut visits = HashMap::new();
+    for &name in graph.keys() {
+        if !visits.contains_key(name) {
+            find_cycles(name, &graph, &mut visits, &mut Vec::new(), &mut errors);
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+struct IncludeRef<'a> {
+    name: &'a str,
+    position: Position,
+    /// Whether rendering the template always renders the include.
+    unconditional: bool,
+}
+
+fn collect_includes<'a>(parts: &'a [Part<'a>], unconditional: bool, out: &mut Vec<IncludeRef<'a>>) {
+    for part in parts {
+        match part {
+            Part::Include(include) => {
+                if let IncludeName::Path(name) = include.name {
+                    out.push(IncludeRef {
+                        name,
+                        position: include.position,
+                        // Nothing is rendered without the context value.
+                        unconditional: unconditional && include.context.is_none(),
+                    });
+                }
+            }
+            Part::Parent(name, position, parts) => {
+                out.push(IncludeRef {
+                    name,
+                    position: *position,
+                    unconditional,
+                });
+                collect_includes(parts, false, out);
+            }
+            Part::Section(_, _, parts)
+            | Part::InvertedSection(_, _, parts)
+            | Part::Block(_, _, parts)
+            | Part::Inline(_, _, parts) => collect_includes(parts, false, out),
+            Part::Text(_) | Part::Variable(..) | Part::UnescapedVariable(..) | Part::Comment => {}
+        }
+    }
+}
+
+fn collect_inlines<'a>(parts: &'a [Part<'a>], out: &mut HashSet<&'a str>) {
+    for part in parts {
+        match part {
+            Part::Inline(name, _, parts) => {
+                out.insert(name);
+                collect_inlines(parts, out);
+            }
+            Part::Section(_, _, parts)
+            | Part::InvertedSection(_, _, parts)
+            | Part::Block(_, _, parts)
+            | Part::Parent(_, _, parts) => collect_inlines(parts, out),
+            _ => {}
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Visit {
+    InProgress,
+    Done,
+}
+
+/// Depth-first search reporting every include that leads back to a template
+/// on `stack`.
+fn find_cycles<'a>(
+    name: &'a str,
+    graph: &BTreeMap<&'a str, Vec<(&'a str, Position)>>,
+    visits: &mut HashMap<&'a str, Visit>,
+    stack: &mut Vec<&'a str>,
+    errors: &mut Vec<ValidationError>,
+) {
+    visits.insert(name, Visit::InProgress);
+    stack.push(name);
+    for &(target, position) in &graph[name] {
+        match visits.get(target) {
+            Some(Visit::InProgress) => {
+                let start = stack.iter().position(|name| *name == target).unwrap();
+                let mut chain: Vec<String> =
+                    stack[start..].iter().map(|name| name.to_string()).collect();
+                chain.push(target.to_owned());
+                errors.push(ValidationError::IncludeCycle {
+                    template: name.to_owned(),
+                    chain,
+                    position,
+                });
+            }
+            Some(Visit::Done) => {}
+            None => find_cycles(target, graph, visits, stack, errors),
+        }
+    }
+    stack.pop();
+    visits.insert(name, Visit::Done);
+}
@@ -0,0 +1,46 @@
+//! Checks that `include_templates!` embeds the same templates `load_dir`
+//! loads.
+
+use templet::{include_templates, LoadOptions, Templates};
+
+#[test]
+fn embeds_template_dir() {
+    let embedded = include_templates!("templates", extensions = ["html"]).unwrap();
+    let loaded = Templates::load_dir("templates", &["html"]).unwrap();
+
+    assert_eq!(embedded.validate(), Ok(()));
+    for name in ["index.html", "partials/header.html", "partials/footer.html"] {
+        assert_eq!(
+            embedded.render_to_string(name, &()).unwrap(),
+            loaded.render_to_string(name, &()).unwrap()
+        );
+    }
+    let unchecked = include_templates!("templates", check = false).unwrap();
+    assert_eq!(
+        unchecked.render_to_string("index.html", &()).unwrap(),
+        loaded.render_to_string("index.html", &()).unwrap()
+    );
+}
+
+#[test]
+fn embeds_with_load_options() {
+    let embedded = include_templates!(
+        "templates",
+        extensions = ["html"],
+        strip_extensions = true,
+        alias = ("home", "index"),
+        check = false,
+    )
+    .unwrap();
+    let options = LoadOptions::new(&["html"])
+        .strip_extensions(true)
+        .alias("home", "index");
+    let loaded = Templates::load_dir_with("templates", &options).unwrap();
+
+    for name in ["home", "partials/header"] {
+        assert_eq!(
+            embedded.render_to_string(name, &()).unwrap(),
+            loaded.render_to_string(name, &()).unwrap()
+        );
+    }
+}
@@ -0,0 +1,169 @@
+//! Runs the Mustache spec fixtures in `tests/spec` against `Templates`.
+//!
+//! Each spec file lists the tests templet is known to fail, so the suite
+//! shows how far templet is from Mustache compatibility and catches
+//! regressions. Run with `--nocapture` to see the report for every file.
+
+use std::{collections::HashMap, fs};
+
+use bevy_reflect::{DynamicList, DynamicStruct, Reflect};
+use serde_json::{Map, Value};
+use templet::{Template, Templates};
+
+#[test]
+fn comments() {
+    run_spec("comments", &[]);
+}
+
+#[test]
+fn delimiters() {
+    // Partial names must be quoted, `{{> "name"}}`.
+    run_spec("delimiters", &["Partial Inheritence", "Post-Partial Behavior"]);
+}
+
+#[test]
+fn interpolation() {
+    run_spec("interpolation", &[]);
+}
+
+#[test]
+fn inverted() {
+    run_spec("inverted", &[]);
+}
+
+#[test]
+fn partials() {
+    run_spec(
+        "partials",
+        // Partial names must be quoted, `{{> "name"}}`.
+        &[
+            "Basic Behavior",
+            "Failed Lookup",
+            "Context",
+            "Recursion",
+            "SubPartials",
+            "Surrounding Whitespace",
+            "Inline Indentation",
+            "Standalone Line Endings",
+            "Standalone Without Previous Line",
+            "Standalone Without Newline",
+            "Standalone Indentation",
+            "Padding Whitespace",
+        ],
+    );
+}
+
+#[test]
+fn sections() {
+    run_spec("sections", &[]);
+}
+
+#[test]
+fn inheritance() {
+    run_spec(
+        "~inheritance",
+        // Parent names must be quoted, `{{< "name"}}`.
+        &[
+            "Inherit",
+            "Overridden content",
+            "Data does not override block",
+            "Data does not override block default",
+            "Overridden parent",
+            "Two overridden parents",
+            "Override parent with newlines",
+            "Inherit indentation",
+            "Only one override",
+            "Parent template",
+            "Recursion",
+            "Multi-level inheritance",
+            "Multi-level inheritance, no sub child",
+            "Text inside parent",
+            "Text inside parent",
+            "Block scope",
+            "Standalone parent",
+            "Standalone block",
+            "Block reindentation",
+            "Intrinsic indentation",
+            "Nested block reindentation",
+        ],
+    );
+}
+
+/// Runs every test of a spec file, checking that exactly the tests in
+/// `known_failures` fail.
+fn run_spec(file: &str, known_failures: &[&str]) {
+    let path = format!("{}/tests/spec/{}.json", env!("CARGO_MANIFEST_DIR"), file);
+    let spec: Value = serde_json::from_str(&fs::read_to_string(path).unwrap()).unwrap();
+    let tests = spec["tests"].as_array().unwrap();
+
+    let mut failures = Vec::new();
+    for test in tests {
+        let name = test["name"].as_str().unwrap();
+        if let Err(reason) = run_test(test) {
+            println!("FAIL {}: {}: {}", file, name, reason);
+            failures.push(name);
+        }
+    }
+    println!(
+        "{}: {}/{} passed",
+        file,
+        tests.len() - failures.len(),
+        tests.len()
+    );
+    assert_eq!(failures, known_failures);
+}
+
+fn run_test(test: &Value) -> Result<(), String> {
+    let mut templates = HashMap::new();
+    templates.insert("test".to_owned(), parse(&test["template"])?);
+    if let Some(partials) = test["partials"].as_object() {
+        for (name, source) in partials {
+            templates.insert(name.clone(), parse(source)?);
+        }
+    }
+
+    let data = to_struct(test["data"].as_object().unwrap());
+    let output = Templates::new(templates)
+        .render_to_string("test", &data)
+        .map_err(|err| err.to_string())?;
+    let expected = test["expected"].as_str().unwrap();
+    if output == expected {
+        Ok(())
+    } else {
+        Err(format!("expected {:?}, got {:?}", expected, output))
+    }
+}
+
+fn parse(source: &Value) -> Result<Template, String> {
+    Template::parse(source.as_str().unwrap().to_owned()).map_err(|err| err.to_string())
+}
+
+/// Converts spec data to a value templet can render. Objects become dynamic
+/// structs, arrays dynamic lists and `null` a `None`.
+fn to_reflect(value: &Value) -> Box<dyn Reflect> {
+    match value {
+        Value::Null => Box::new(None::<String>),
+        Value::Bool(b) => Box::new(*b),
+        Value::Number(n) => match n.as_i64() {
+            Some(n) => Box::new(n),
+            None => Box::new(n.as_f64().unwrap()),
+        },
+        Value::String(s) => Box::new(s.clone()),
+        Value::Array(items) => {
+            let mut list = DynamicList::default();
+            for item in items {
+                list.push_box(to_reflect(item));
+            }
+            Box::new(list)
+        }
+        Value::Object(object) => Box::new(to_struct(object)),
+    }
+}
+
+fn to_struct(object: &Map<String, Value>) -> DynamicStruct {
+    let mut st = DynamicStruct::default();
+    for (name, value) in object {
+        st.insert_boxed(name, to_reflect(value));
+    }
+    st
+}